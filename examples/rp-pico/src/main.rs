@@ -101,7 +101,7 @@ fn main() -> ! {
     );
 
     // Configure the AHT20 temperature and humidity sensor.
-    let mut aht20_uninit = aht20_driver::AHT20::new(i2c, aht20_driver::SENSOR_ADDRESS);
+    let aht20_uninit = aht20_driver::AHT20::new(i2c, aht20_driver::SENSOR_ADDRESS);
     let mut aht20 = aht20_uninit.init(&mut timer).unwrap();
     defmt::info!("setup done");
 
@@ -0,0 +1,192 @@
+//! Learning the sensor's conversion time.
+//!
+//! The datasheet asks for an 80ms wait after triggering a measurement, but many sensors finish
+//! well before that. With adaptive timing turned on through `AHT20Builder::adaptive_timing` the
+//! driver keeps track of how long measurements actually take, and shortens the initial wait to a
+//! percentile of those, plus a safety margin. This cuts both the latency and the time a battery
+//! powered node needs to stay awake.
+//!
+//! The completion time is worked out from the number of status polls needed after the initial
+//! wait. To be able to see how short the conversion is, the first few measurements wait only
+//! `AdaptiveTiming::learning_wait_ms` and poll from there. After that, a measurement that needed
+//! no polls only tells us the conversion was at most as long as the wait, so it is recorded as the
+//! current estimate, which keeps the estimate from drifting.
+//!
+//! If the sensor ever contradicts a ready status with `Error::UnexpectedBusy`, everything learned
+//! is thrown away and the datasheet wait is used for a while before learning starts again.
+
+/// How many conversion times are kept to work out the percentile from.
+const WINDOW: usize = 8;
+
+/// Settings for adaptive timing. The defaults wait for the 90th percentile plus 5ms.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "use-defmt", derive(defmt::Format))]
+pub struct AdaptiveTiming {
+    /// Which percentile of the observed conversion times to wait for, from 1 to 100.
+    pub percentile: u8,
+    /// Milliseconds added to the percentile as a safety margin.
+    pub margin_ms: u32,
+    /// The initial wait while learning, in milliseconds. This should be shorter than any
+    /// conversion is expected to take.
+    pub learning_wait_ms: u32,
+}
+
+impl Default for AdaptiveTiming {
+    fn default() -> Self {
+        AdaptiveTiming {
+            percentile: 90,
+            margin_ms: 5,
+            learning_wait_ms: 30,
+        }
+    }
+}
+
+impl AdaptiveTiming {
+    /// Check that the percentile is in range.
+    pub(crate) fn is_valid(&self) -> bool {
+        (1..=100).contains(&self.percentile)
+    }
+}
+
+/// The conversion times seen so far, and what to wait for next.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct ConversionLearner {
+    settings: AdaptiveTiming,
+    samples: [u32; WINDOW],
+    len: usize,
+    next: usize,
+    /// Measurements left to do with the datasheet wait after an `UnexpectedBusy`.
+    fallback_remaining: usize,
+}
+
+impl ConversionLearner {
+    pub(crate) fn new(settings: AdaptiveTiming) -> Self {
+        ConversionLearner {
+            settings,
+            samples: [0; WINDOW],
+            len: 0,
+            next: 0,
+            fallback_remaining: 0,
+        }
+    }
+
+    /// How long to wait after triggering a measurement, never more than `datasheet_ms`.
+    pub(crate) fn initial_wait_ms(&self, datasheet_ms: u32) -> u32 {
+        if self.fallback_remaining > 0 {
+            datasheet_ms
+        } else if self.len < WINDOW {
+            self.settings.learning_wait_ms.min(datasheet_ms)
+        } else {
+            self.percentile_ms()
+                .saturating_add(self.settings.margin_ms)
+                .min(datasheet_ms)
+        }
+    }
+
+    /// Record a completed measurement that waited `waited_ms` and then polled `polls` times.
+    pub(crate) fn record(&mut self, waited_ms: u32, polls: u32, poll_interval_ms: u32) {
+        if self.fallback_remaining > 0 {
+            self.fallback_remaining -= 1;
+            return;
+        }
+
+        let observed_ms = if polls > 0 || self.len < WINDOW {
+            waited_ms.saturating_add(polls.saturating_mul(poll_interval_ms))
+        } else {
+            // Finished within the wait. All we know is that the estimate was long enough.
+            waited_ms.saturating_sub(self.settings.margin_ms)
+        };
+
+        self.samples[self.next] = observed_ms;
+        self.next = (self.next + 1) % WINDOW;
+        self.len = (self.len + 1).min(WINDOW);
+    }
+
+    /// Forget what has been learned, and use the datasheet wait for the next `WINDOW`
+    /// measurements.
+    pub(crate) fn fall_back(&mut self) {
+        *self = ConversionLearner::new(self.settings);
+        self.fallback_remaining = WINDOW;
+    }
+
+    /// The configured percentile of the recorded conversion times.
+    fn percentile_ms(&self) -> u32 {
+        let mut sorted = self.samples;
+        let sorted = &mut sorted[..self.len];
+        sorted.sort_unstable();
+        // Nearest-rank percentile.
+        let rank = (usize::from(self.settings.percentile) * self.len).div_ceil(100);
+        sorted[rank.max(1) - 1]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AdaptiveTiming, ConversionLearner, WINDOW};
+
+    /// While learning, the short learning wait is used.
+    #[test]
+    fn learning_uses_learning_wait() {
+        let learner = ConversionLearner::new(AdaptiveTiming::default());
+        assert_eq!(learner.initial_wait_ms(80), 30);
+    }
+
+    /// Once learned, the wait is the percentile plus the margin.
+    #[test]
+    fn learned_wait_is_percentile_plus_margin() {
+        let mut learner = ConversionLearner::new(AdaptiveTiming::default());
+        for polls in [12, 14, 15, 15, 16, 16, 17, 25] {
+            learner.record(30, polls, 1);
+        }
+        // The 90th percentile of 8 samples is the highest, 55ms.
+        assert_eq!(learner.initial_wait_ms(80), 60);
+
+        let mut learner = ConversionLearner::new(AdaptiveTiming {
+            percentile: 50,
+            ..AdaptiveTiming::default()
+        });
+        for polls in [12, 14, 15, 15, 16, 16, 17, 25] {
+            learner.record(30, polls, 1);
+        }
+        assert_eq!(learner.initial_wait_ms(80), 50);
+    }
+
+    /// The wait is never longer than the datasheet's.
+    #[test]
+    fn learned_wait_capped_at_datasheet() {
+        let mut learner = ConversionLearner::new(AdaptiveTiming::default());
+        for _ in 0..WINDOW {
+            learner.record(30, 70, 1);
+        }
+        assert_eq!(learner.initial_wait_ms(80), 80);
+    }
+
+    /// Measurements that needed no polling keep the estimate where it is.
+    #[test]
+    fn learned_wait_is_stable() {
+        let mut learner = ConversionLearner::new(AdaptiveTiming::default());
+        for _ in 0..WINDOW {
+            learner.record(30, 20, 1);
+        }
+        let wait = learner.initial_wait_ms(80);
+        for _ in 0..(2 * WINDOW) {
+            learner.record(wait, 0, 1);
+        }
+        assert_eq!(learner.initial_wait_ms(80), wait);
+    }
+
+    /// After falling back, the datasheet wait is used until learning starts over.
+    #[test]
+    fn fall_back_uses_datasheet_wait() {
+        let mut learner = ConversionLearner::new(AdaptiveTiming::default());
+        for _ in 0..WINDOW {
+            learner.record(30, 20, 1);
+        }
+        learner.fall_back();
+        for _ in 0..WINDOW {
+            assert_eq!(learner.initial_wait_ms(80), 80);
+            learner.record(80, 0, 1);
+        }
+        assert_eq!(learner.initial_wait_ms(80), 30);
+    }
+}
@@ -0,0 +1,296 @@
+//! Threshold alarms with hysteresis and debounce.
+//!
+//! `Alarms` checks each reading against a fixed set of `AlarmRule`s, and reports when an alarm is
+//! raised or cleared. Two things keep an alarm from chattering when a reading hovers around its
+//! limit:
+//!
+//! * Hysteresis: once raised, a high alarm only clears when the value drops `hysteresis` below the
+//!   limit, and a low alarm when it rises `hysteresis` above it.
+//! * Debounce: the condition has to hold for `raise_after_ms` before the alarm is raised, and for
+//!   `clear_after_ms` before it is cleared.
+//!
+//! An alarm for humidity staying above 70% for a minute, which clears below 65%:
+//!
+//!     use aht20_driver::{AlarmEvent, AlarmRule, Alarms, Limit, Quantity, SensorReading};
+//!
+//!     let mut alarms = Alarms::new([AlarmRule {
+//!         quantity: Quantity::Humidity,
+//!         limit: Limit::High(70.0),
+//!         hysteresis: 5.0,
+//!         raise_after_ms: 60_000,
+//!         clear_after_ms: 60_000,
+//!     }])
+//!     .unwrap();
+//!
+//!     let damp = SensorReading { humidity: 75.0, temperature: 21.0 };
+//!     assert!(alarms.evaluate(damp, 0).is_empty());
+//!     let events = alarms.evaluate(damp, 60_000);
+//!     assert_eq!(events[0], AlarmEvent::Raised { rule: 0, value: 75.0 });
+
+use heapless::Vec;
+
+use crate::{Quantity, SensorReading};
+
+/// The limit of an `AlarmRule`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "use-defmt", derive(defmt::Format))]
+pub enum Limit {
+    /// Alarm when the value is above this.
+    High(f32),
+    /// Alarm when the value is below this.
+    Low(f32),
+}
+
+/// When to raise and clear one alarm.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "use-defmt", derive(defmt::Format))]
+pub struct AlarmRule {
+    /// What the limit applies to.
+    pub quantity: Quantity,
+    /// The limit, in the quantity's units.
+    pub limit: Limit,
+    /// How far back past the limit the value has to go for the alarm to clear.
+    pub hysteresis: f32,
+    /// How long the limit has to be exceeded before the alarm is raised, in milliseconds.
+    pub raise_after_ms: u64,
+    /// How long the value has to be back within the hysteresis band before the alarm is cleared,
+    /// in milliseconds.
+    pub clear_after_ms: u64,
+}
+
+impl AlarmRule {
+    /// Whether `value` is bad enough to raise the alarm.
+    fn exceeded(&self, value: f32) -> bool {
+        match self.limit {
+            Limit::High(limit) => value > limit,
+            Limit::Low(limit) => value < limit,
+        }
+    }
+
+    /// Whether `value` is good enough to clear the alarm.
+    fn recovered(&self, value: f32) -> bool {
+        match self.limit {
+            Limit::High(limit) => value < limit - self.hysteresis,
+            Limit::Low(limit) => value > limit + self.hysteresis,
+        }
+    }
+
+    /// Check that the limit and hysteresis are finite, and the hysteresis isn't negative.
+    fn is_valid(&self) -> bool {
+        let limit = match self.limit {
+            Limit::High(limit) | Limit::Low(limit) => limit,
+        };
+        limit.is_finite() && self.hysteresis.is_finite() && self.hysteresis >= 0.0
+    }
+}
+
+/// A change in an alarm's state, from `Alarms::evaluate`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "use-defmt", derive(defmt::Format))]
+pub enum AlarmEvent {
+    /// The alarm for the rule at index `rule` was raised, at this value.
+    Raised { rule: usize, value: f32 },
+    /// The alarm for the rule at index `rule` was cleared, at this value.
+    Cleared { rule: usize, value: f32 },
+}
+
+/// The state of one rule's alarm.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+struct AlarmState {
+    active: bool,
+    /// Since when the alarm has been due to change state, if it has.
+    pending_since_ms: Option<u64>,
+}
+
+/// The error from `Alarms::new`, for a rule whose limit or hysteresis isn't a finite number, or
+/// whose hysteresis is negative.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "use-defmt", derive(defmt::Format))]
+pub struct InvalidAlarmRule;
+
+impl core::fmt::Display for InvalidAlarmRule {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> Result<(), core::fmt::Error> {
+        write!(f, "invalid alarm rule")
+    }
+}
+
+impl core::error::Error for InvalidAlarmRule {}
+
+/// Checks readings against `N` alarm rules.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Alarms<const N: usize> {
+    rules: [AlarmRule; N],
+    states: [AlarmState; N],
+}
+
+impl<const N: usize> Alarms<N> {
+    /// Create the alarms, all cleared. Returns `InvalidAlarmRule` if a rule's limit or hysteresis
+    /// isn't finite, or its hysteresis is negative.
+    pub fn new(rules: [AlarmRule; N]) -> Result<Self, InvalidAlarmRule> {
+        if !rules.iter().all(AlarmRule::is_valid) {
+            return Err(InvalidAlarmRule);
+        }
+        Ok(Alarms {
+            rules,
+            states: [AlarmState::default(); N],
+        })
+    }
+
+    /// Check a reading taken at `timestamp_ms` against every rule, and return the alarms raised
+    /// and cleared by it, in rule order.
+    pub fn evaluate(&mut self, reading: SensorReading, timestamp_ms: u64) -> Vec<AlarmEvent, N> {
+        let mut events = Vec::new();
+        for (index, (rule, state)) in self.rules.iter().zip(self.states.iter_mut()).enumerate() {
+            let value = reading.get(rule.quantity);
+            let (due, after_ms) = if state.active {
+                (rule.recovered(value), rule.clear_after_ms)
+            } else {
+                (rule.exceeded(value), rule.raise_after_ms)
+            };
+
+            if !due {
+                state.pending_since_ms = None;
+                continue;
+            }
+            let since_ms = *state.pending_since_ms.get_or_insert(timestamp_ms);
+            if timestamp_ms.saturating_sub(since_ms) < after_ms {
+                continue;
+            }
+
+            state.active = !state.active;
+            state.pending_since_ms = None;
+            let event = if state.active {
+                AlarmEvent::Raised { rule: index, value }
+            } else {
+                AlarmEvent::Cleared { rule: index, value }
+            };
+            // There is room for one event per rule.
+            let _ = events.push(event);
+        }
+        events
+    }
+
+    /// Whether the alarm for the rule at index `rule` is raised. `false` for an index out of
+    /// range.
+    pub fn is_active(&self, rule: usize) -> bool {
+        self.states.get(rule).is_some_and(|state| state.active)
+    }
+
+    /// The rules, in the order given to `new`.
+    pub fn rules(&self) -> &[AlarmRule; N] {
+        &self.rules
+    }
+
+    /// Clear every alarm without reporting it, for example after a gap in readings.
+    pub fn reset(&mut self) {
+        self.states = [AlarmState::default(); N];
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AlarmEvent, AlarmRule, Alarms, InvalidAlarmRule, Limit};
+    use crate::test_support::at_temperature;
+    use crate::{Quantity, SensorReading};
+
+    fn rule(limit: Limit) -> AlarmRule {
+        AlarmRule {
+            quantity: Quantity::Temperature,
+            limit,
+            hysteresis: 1.0,
+            raise_after_ms: 10_000,
+            clear_after_ms: 20_000,
+        }
+    }
+
+    /// An alarm is only raised once the limit has been exceeded long enough.
+    #[test]
+    fn raise_debounced() {
+        let mut alarms = Alarms::new([rule(Limit::High(30.0))]).unwrap();
+        assert!(alarms.evaluate(at_temperature(31.0), 0).is_empty());
+        // Dipping back under the limit restarts the wait.
+        assert!(alarms.evaluate(at_temperature(29.0), 5000).is_empty());
+        assert!(alarms.evaluate(at_temperature(31.0), 8000).is_empty());
+        assert!(alarms.evaluate(at_temperature(31.0), 17_999).is_empty());
+        assert!(!alarms.is_active(0));
+
+        let events = alarms.evaluate(at_temperature(32.0), 18_000);
+        assert_eq!(
+            events[..],
+            [AlarmEvent::Raised {
+                rule: 0,
+                value: 32.0
+            }]
+        );
+        assert!(alarms.is_active(0));
+        assert!(alarms.evaluate(at_temperature(32.0), 19_000).is_empty());
+    }
+
+    /// An alarm only clears once back past the hysteresis band for long enough.
+    #[test]
+    fn clear_with_hysteresis() {
+        let mut alarms = Alarms::new([rule(Limit::Low(5.0))]).unwrap();
+        alarms.evaluate(at_temperature(4.0), 0);
+        alarms.evaluate(at_temperature(4.0), 10_000);
+        assert!(alarms.is_active(0));
+
+        // Above the limit, but within the hysteresis band.
+        assert!(alarms.evaluate(at_temperature(5.5), 20_000).is_empty());
+        assert!(alarms.evaluate(at_temperature(5.5), 60_000).is_empty());
+        assert!(alarms.is_active(0));
+
+        assert!(alarms.evaluate(at_temperature(6.5), 70_000).is_empty());
+        let events = alarms.evaluate(at_temperature(6.5), 90_000);
+        assert_eq!(
+            events[..],
+            [AlarmEvent::Cleared {
+                rule: 0,
+                value: 6.5
+            }]
+        );
+        assert!(!alarms.is_active(0));
+    }
+
+    /// Each rule reports separately, including ones on the dew point.
+    #[test]
+    fn multiple_rules() {
+        let dew_point_rule = AlarmRule {
+            quantity: Quantity::DewPoint,
+            limit: Limit::High(15.0),
+            hysteresis: 0.5,
+            raise_after_ms: 0,
+            clear_after_ms: 0,
+        };
+        let mut alarms = Alarms::new([rule(Limit::High(20.0)), dew_point_rule]).unwrap();
+
+        // 25C and 60% has a dew point of around 16.7C.
+        let muggy = SensorReading {
+            humidity: 60.0,
+            temperature: 25.0,
+        };
+        let events = alarms.evaluate(muggy, 0);
+        assert_eq!(events.len(), 1);
+        assert!(matches!(events[0], AlarmEvent::Raised { rule: 1, .. }));
+        let events = alarms.evaluate(muggy, 10_000);
+        assert_eq!(
+            events[..],
+            [AlarmEvent::Raised {
+                rule: 0,
+                value: 25.0
+            }]
+        );
+
+        alarms.reset();
+        assert!(!alarms.is_active(0) && !alarms.is_active(1));
+    }
+
+    /// Rules with a negative or missing hysteresis are rejected.
+    #[test]
+    fn rejects_invalid_rules() {
+        let mut bad = rule(Limit::High(30.0));
+        bad.hysteresis = -1.0;
+        assert_eq!(Alarms::new([bad]), Err(InvalidAlarmRule));
+        let bad = rule(Limit::Low(f32::NAN));
+        assert_eq!(Alarms::new([bad]), Err(InvalidAlarmRule));
+    }
+}
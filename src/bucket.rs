@@ -0,0 +1,199 @@
+//! Summarizing readings into fixed-length time buckets.
+//!
+//! A node that measures every 2 seconds but uploads 5-minute summaries can push every reading into
+//! a `BucketAggregator`, and upload the `BucketSummary` it hands back whenever a bucket closes.
+//! Buckets are aligned to multiples of their length on the clock used for the timestamps.
+//!
+//!     use aht20_driver::{BucketAggregator, SensorReading};
+//!
+//!     let mut aggregator = BucketAggregator::new(5 * 60 * 1000).unwrap();
+//!     # let reading = SensorReading { humidity: 40.0, temperature: 21.0 };
+//!     # let uptime_ms = 0;
+//!     if let Some(summary) = aggregator.push(reading, uptime_ms) {
+//!         // Upload summary.
+//!     }
+//!
+//! A bucket closes when a reading for a later bucket comes in, or when `close_due` is called after
+//! the bucket's end, so a gap in readings doesn't hold a summary back. Buckets without any
+//! readings are skipped rather than summarized, so a gap shows up as a jump in `start_ms` between
+//! summaries. A reading for a bucket that has already closed, or that is older than the open
+//! bucket, arrives too late to be counted. It is dropped, and counted in `late_samples`.
+
+use crate::{ReadingStatistics, SensorReading};
+
+/// The summary of one closed bucket.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BucketSummary {
+    /// When the bucket started, in milliseconds.
+    pub start_ms: u64,
+    /// The length of the bucket, in milliseconds.
+    pub length_ms: u64,
+    /// The min, mean, max and count of the readings in the bucket.
+    pub statistics: ReadingStatistics,
+}
+
+/// The error from `BucketAggregator::new`, as buckets can't be 0ms long.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "use-defmt", derive(defmt::Format))]
+pub struct ZeroBucketLength;
+
+impl core::fmt::Display for ZeroBucketLength {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> Result<(), core::fmt::Error> {
+        write!(f, "bucket length must not be zero")
+    }
+}
+
+impl core::error::Error for ZeroBucketLength {}
+
+/// Groups timestamped readings into buckets, and summarizes each bucket when it closes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BucketAggregator {
+    length_ms: u64,
+    /// The start of the open bucket, and its readings so far.
+    open: Option<(u64, ReadingStatistics)>,
+    /// The end of the last closed bucket. Readings before this are late.
+    closed_until_ms: u64,
+    late_samples: u32,
+}
+
+impl BucketAggregator {
+    /// Create an aggregator for buckets `length_ms` long. Returns `ZeroBucketLength` if the length
+    /// is 0.
+    pub fn new(length_ms: u64) -> Result<Self, ZeroBucketLength> {
+        if length_ms == 0 {
+            return Err(ZeroBucketLength);
+        }
+        Ok(BucketAggregator {
+            length_ms,
+            open: None,
+            closed_until_ms: 0,
+            late_samples: 0,
+        })
+    }
+
+    /// Add a reading taken at `timestamp_ms`.
+    ///
+    /// If the reading belongs to a later bucket than the open one, the open bucket is closed and
+    /// its summary returned.
+    pub fn push(&mut self, reading: SensorReading, timestamp_ms: u64) -> Option<BucketSummary> {
+        let start_ms = timestamp_ms - timestamp_ms % self.length_ms;
+
+        let open_start_ms = self.open.map(|(open_start_ms, _)| open_start_ms);
+        if start_ms < self.closed_until_ms || open_start_ms.is_some_and(|open| start_ms < open) {
+            #[cfg(feature = "use-defmt")]
+            defmt::debug!("BucketAggregator: dropping a late reading.");
+            self.late_samples = self.late_samples.saturating_add(1);
+            return None;
+        }
+
+        let closed = match open_start_ms {
+            Some(open_start_ms) if start_ms > open_start_ms => self.flush(),
+            _ => None,
+        };
+
+        let (_, statistics) = self
+            .open
+            .get_or_insert((start_ms, ReadingStatistics::default()));
+        statistics.add(reading);
+        closed
+    }
+
+    /// Close the open bucket if it ended before `now_ms`, and return its summary.
+    ///
+    /// Call this periodically so that a bucket is summarized on time even when readings stop.
+    pub fn close_due(&mut self, now_ms: u64) -> Option<BucketSummary> {
+        match self.open {
+            Some((start_ms, _)) if now_ms >= start_ms.saturating_add(self.length_ms) => {
+                self.flush()
+            }
+            _ => None,
+        }
+    }
+
+    /// Close the open bucket now, however far into it we are, and return its summary. For example
+    /// before powering down. Later readings for the same bucket count as late.
+    pub fn flush(&mut self) -> Option<BucketSummary> {
+        let (start_ms, statistics) = self.open.take()?;
+        self.closed_until_ms = start_ms.saturating_add(self.length_ms);
+        Some(BucketSummary {
+            start_ms,
+            length_ms: self.length_ms,
+            statistics,
+        })
+    }
+
+    /// How many readings have been dropped for arriving after their bucket closed.
+    pub fn late_samples(&self) -> u32 {
+        self.late_samples
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BucketAggregator, ZeroBucketLength};
+    use crate::test_support::at_temperature;
+
+    /// Readings are summarized when a reading for the next bucket arrives.
+    #[test]
+    fn closes_on_next_bucket() {
+        let mut aggregator = BucketAggregator::new(10_000).unwrap();
+        assert_eq!(aggregator.push(at_temperature(20.0), 12_000), None);
+        assert_eq!(aggregator.push(at_temperature(22.0), 15_000), None);
+        assert_eq!(aggregator.push(at_temperature(24.0), 19_999), None);
+
+        let summary = aggregator.push(at_temperature(30.0), 20_000).unwrap();
+        assert_eq!(summary.start_ms, 10_000);
+        assert_eq!(summary.length_ms, 10_000);
+        let temperature = summary.statistics.temperature;
+        assert_eq!(temperature.count(), 3);
+        assert_eq!(temperature.min(), Some(20.0));
+        assert_eq!(temperature.mean(), Some(22.0));
+        assert_eq!(temperature.max(), Some(24.0));
+
+        let summary = aggregator.flush().unwrap();
+        assert_eq!(summary.start_ms, 20_000);
+        assert_eq!(summary.statistics.temperature.count(), 1);
+        assert_eq!(aggregator.flush(), None);
+    }
+
+    /// Empty buckets in a gap are skipped, and close_due closes a bucket without new readings.
+    #[test]
+    fn gaps() {
+        let mut aggregator = BucketAggregator::new(10_000).unwrap();
+        aggregator.push(at_temperature(20.0), 1000);
+        assert_eq!(aggregator.close_due(9999), None);
+        let summary = aggregator.close_due(10_000).unwrap();
+        assert_eq!(summary.start_ms, 0);
+
+        aggregator.push(at_temperature(20.0), 55_000);
+        let summary = aggregator.push(at_temperature(20.0), 95_000).unwrap();
+        assert_eq!(summary.start_ms, 50_000);
+        assert_eq!(aggregator.flush().unwrap().start_ms, 90_000);
+    }
+
+    /// Readings for a bucket that has already closed are dropped and counted.
+    #[test]
+    fn late_samples() {
+        let mut aggregator = BucketAggregator::new(10_000).unwrap();
+        aggregator.push(at_temperature(20.0), 25_000);
+        assert_eq!(aggregator.push(at_temperature(99.0), 19_000), None);
+        assert_eq!(aggregator.late_samples(), 1);
+        // A reading out of order within the open bucket still counts.
+        aggregator.push(at_temperature(22.0), 21_000);
+
+        let temperature = aggregator.flush().unwrap().statistics.temperature;
+        assert_eq!(temperature.count(), 2);
+        assert_eq!(temperature.max(), Some(22.0));
+
+        // The bucket is closed, even though there isn't an open one after it.
+        assert_eq!(aggregator.push(at_temperature(99.0), 29_000), None);
+        assert_eq!(aggregator.late_samples(), 2);
+        assert_eq!(aggregator.flush(), None);
+    }
+
+    /// A bucket length of 0 is rejected.
+    #[test]
+    fn rejects_zero_length() {
+        assert_eq!(BucketAggregator::new(0), Err(ZeroBucketLength));
+    }
+}
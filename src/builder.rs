@@ -0,0 +1,409 @@
+//! Builder-based driver configuration.
+//!
+//! `AHT20::new` covers the common case. Everything else the driver can be told - which variant it
+//! is talking to, timing overrides, how many times to retry a failed measurement, how strictly to
+//! validate the sensor's response, how to poll for a finished measurement, offsets to compensate
+//! for a sensor's placement, and whether to learn the conversion time - is set through
+//! `AHT20::builder`:
+//!
+//!     # use embedded_hal_mock::eh1::i2c::Mock as I2cMock;
+//!     use aht20_driver::{Compensation, Validation, Variant, AHT20, SENSOR_ADDRESS};
+//!
+//!     # let mock_i2c = I2cMock::new(&[]);
+//!     let aht20 = AHT20::builder(mock_i2c)
+//!         .address(SENSOR_ADDRESS)
+//!         .variant(Variant::DHT20)
+//!         .measurement_delay_ms(90)
+//!         .retry_budget(3)
+//!         .validation(Validation::Strict)
+//!         .compensation(Compensation {
+//!             temperature_offset: -0.8,
+//!             humidity_offset: 1.5,
+//!         })
+//!         .build()
+//!         .unwrap();
+//!     # aht20.destroy().done();
+
+use core::marker::PhantomData;
+
+use embedded_hal::i2c::I2c;
+
+use crate::{
+    AdaptiveTiming, Calibration, ConversionLearner, Uninit, Variant, AHT20, SENSOR_ADDRESS,
+};
+
+/// How strictly the 7 byte measurement frame from the sensor is checked.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "use-defmt", derive(defmt::Format))]
+pub enum Validation {
+    /// Check the CRC, and that the CRC-checked status byte agrees that the measurement is ready.
+    /// This is the default.
+    #[default]
+    Strict,
+    /// Check the CRC, but not the repeated status byte.
+    CrcOnly,
+    /// Accept the frame without any checks. Only use this for parts that don't send a CRC.
+    None,
+}
+
+/// How the driver finds out that a measurement is ready.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "use-defmt", derive(defmt::Format))]
+pub enum PollMode {
+    /// Read the status byte until the sensor is ready, then read the 7 byte frame. This is the
+    /// default, and what the datasheet describes.
+    #[default]
+    StatusByte,
+    /// Read the whole 7 byte frame each time, and use the status byte at its start. A measurement
+    /// then costs one read fewer, which helps on a busy shared bus. A ready frame whose CRC
+    /// doesn't match is read again a couple of times before the measurement fails.
+    FullFrame,
+}
+
+/// Fixed offsets added to every reading, to compensate for how a sensor is mounted.
+///
+/// For example, a sensor next to a warm regulator might read 0.8°C high, which a
+/// `temperature_offset` of `-0.8` corrects. Humidity is clamped to 0-100% after the offset is
+/// applied. The offsets are applied by `measure`, but not by `measure_no_fp` as that would need
+/// floating point math.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Compensation {
+    /// Added to the temperature, in degrees Celsius.
+    pub temperature_offset: f32,
+    /// Added to the relative humidity, in percent.
+    pub humidity_offset: f32,
+}
+
+/// Errors from `AHT20Builder::build` for settings that can't work together.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "use-defmt", derive(defmt::Format))]
+pub enum ConfigError {
+    /// The address doesn't fit in 7 bits.
+    InvalidAddress,
+    /// A poll interval of 0ms would have the driver hammer the bus while the sensor is busy.
+    ZeroPollInterval,
+    /// A compensation offset isn't a finite number, or would move humidity by 100% or more.
+    InvalidCompensation,
+    /// The adaptive timing percentile isn't between 1 and 100.
+    InvalidAdaptiveTiming,
+}
+
+impl core::fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> Result<(), core::fmt::Error> {
+        match self {
+            ConfigError::InvalidAddress => write!(f, "I2C address is not a 7-bit address"),
+            ConfigError::ZeroPollInterval => write!(f, "poll interval must not be zero"),
+            ConfigError::InvalidCompensation => write!(f, "invalid compensation offset"),
+            ConfigError::InvalidAdaptiveTiming => write!(f, "invalid adaptive timing percentile"),
+        }
+    }
+}
+
+impl core::error::Error for ConfigError {}
+
+/// A failed `AHT20Builder::build`, with the I2C bus so it isn't lost.
+///
+/// Use `error` to see which setting was rejected, and `into_inner` to get the bus back and try
+/// again. `?` turns it into the `ConfigError` alone.
+pub struct BuildError<I> {
+    i2c: I,
+    error: ConfigError,
+}
+
+impl<I> BuildError<I> {
+    /// Which setting was rejected.
+    pub fn error(&self) -> ConfigError {
+        self.error
+    }
+
+    /// The I2C bus the builder was given.
+    pub fn into_inner(self) -> I {
+        self.i2c
+    }
+
+    /// The I2C bus, and which setting was rejected.
+    pub fn into_parts(self) -> (I, ConfigError) {
+        (self.i2c, self.error)
+    }
+}
+
+impl<I> core::fmt::Debug for BuildError<I> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("BuildError")
+            .field("error", &self.error)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<I> core::fmt::Display for BuildError<I> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> Result<(), core::fmt::Error> {
+        write!(f, "invalid driver configuration: {}", self.error)
+    }
+}
+
+impl<I> core::error::Error for BuildError<I> {}
+
+impl<I> From<BuildError<I>> for ConfigError {
+    fn from(build_error: BuildError<I>) -> Self {
+        build_error.error
+    }
+}
+
+/// Everything the driver has been configured with. The builder fills this in, `AHT20::new` uses
+/// the defaults.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Config {
+    pub(crate) address: u8,
+    pub(crate) variant: Variant,
+    /// How many times a measurement is retried after a CRC or busy mismatch. `None` retries until
+    /// a measurement succeeds.
+    pub(crate) retry_budget: Option<u8>,
+    pub(crate) validation: Validation,
+    pub(crate) poll_mode: PollMode,
+    pub(crate) compensation: Compensation,
+}
+
+impl Config {
+    pub(crate) fn new(address: u8, variant: Variant) -> Self {
+        Config {
+            address,
+            variant,
+            retry_budget: None,
+            validation: Validation::Strict,
+            poll_mode: PollMode::StatusByte,
+            compensation: Compensation::default(),
+        }
+    }
+}
+
+/// Collects the driver configuration, returned by `AHT20::builder`.
+///
+/// Timing overrides are applied on top of the chosen variant, regardless of the order the methods
+/// are called in.
+pub struct AHT20Builder<I>
+where
+    I: I2c,
+{
+    i2c: I,
+    config: Config,
+    power_on_delay_ms: Option<u32>,
+    init_delay_ms: Option<u32>,
+    measurement_delay_ms: Option<u32>,
+    poll_interval_ms: Option<u32>,
+    adaptive_timing: Option<AdaptiveTiming>,
+}
+
+impl<I> AHT20<I, Uninit>
+where
+    I: I2c,
+{
+    /// Start configuring a driver for the sensor on the I2C bus `I`.
+    ///
+    /// Without any further settings this builds the same driver as
+    /// `AHT20::new(i2c, SENSOR_ADDRESS)`.
+    pub fn builder(i2c: I) -> AHT20Builder<I> {
+        AHT20Builder {
+            i2c,
+            config: Config::new(SENSOR_ADDRESS, Variant::AHT20),
+            power_on_delay_ms: None,
+            init_delay_ms: None,
+            measurement_delay_ms: None,
+            poll_interval_ms: None,
+            adaptive_timing: None,
+        }
+    }
+}
+
+impl<I> AHT20Builder<I>
+where
+    I: I2c,
+{
+    /// The sensor's I2C address. Defaults to `SENSOR_ADDRESS`.
+    pub fn address(mut self, address: u8) -> Self {
+        self.config.address = address;
+        self
+    }
+
+    /// The variant profile to use. Defaults to `Variant::AHT20`.
+    pub fn variant(mut self, variant: Variant) -> Self {
+        self.config.variant = variant;
+        self
+    }
+
+    /// Override the variant's wait after power-on.
+    pub fn power_on_delay_ms(mut self, ms: u32) -> Self {
+        self.power_on_delay_ms = Some(ms);
+        self
+    }
+
+    /// Override the variant's wait after sending `Command::Initialize`.
+    pub fn init_delay_ms(mut self, ms: u32) -> Self {
+        self.init_delay_ms = Some(ms);
+        self
+    }
+
+    /// Override the variant's wait between triggering a measurement and polling for it.
+    pub fn measurement_delay_ms(mut self, ms: u32) -> Self {
+        self.measurement_delay_ms = Some(ms);
+        self
+    }
+
+    /// Override the variant's interval between status polls while the sensor is busy.
+    pub fn poll_interval_ms(mut self, ms: u32) -> Self {
+        self.poll_interval_ms = Some(ms);
+        self
+    }
+
+    /// Give up on a measurement after this many retries, returning the last `InvalidCrc` or
+    /// `UnexpectedBusy` error. By default the driver retries until it gets a good measurement.
+    pub fn retry_budget(mut self, retries: u8) -> Self {
+        self.config.retry_budget = Some(retries);
+        self
+    }
+
+    /// How strictly to check the measurement frame. Defaults to `Validation::Strict`.
+    pub fn validation(mut self, validation: Validation) -> Self {
+        self.config.validation = validation;
+        self
+    }
+
+    /// How to poll for a finished measurement. Defaults to `PollMode::StatusByte`.
+    pub fn poll_mode(mut self, poll_mode: PollMode) -> Self {
+        self.config.poll_mode = poll_mode;
+        self
+    }
+
+    /// Offsets to add to every reading. Defaults to no compensation.
+    pub fn compensation(mut self, compensation: Compensation) -> Self {
+        self.config.compensation = compensation;
+        self
+    }
+
+    /// Learn how long the sensor takes to measure, and wait only that long rather than the
+    /// variant's full measurement delay. Off by default, see `AdaptiveTiming`.
+    pub fn adaptive_timing(mut self, settings: AdaptiveTiming) -> Self {
+        self.adaptive_timing = Some(settings);
+        self
+    }
+
+    /// Check the configuration and create the driver. A rejected configuration returns a
+    /// `BuildError`, which holds the I2C bus.
+    pub fn build(self) -> Result<AHT20<I, Uninit>, BuildError<I>> {
+        let mut config = self.config;
+        let variant = &mut config.variant;
+        variant.power_on_delay_ms = self.power_on_delay_ms.unwrap_or(variant.power_on_delay_ms);
+        variant.init_delay_ms = self.init_delay_ms.unwrap_or(variant.init_delay_ms);
+        variant.measurement_delay_ms = self
+            .measurement_delay_ms
+            .unwrap_or(variant.measurement_delay_ms);
+        variant.poll_interval_ms = self.poll_interval_ms.unwrap_or(variant.poll_interval_ms);
+
+        let rejected = if config.address > 0x7F {
+            Some(ConfigError::InvalidAddress)
+        } else if config.variant.poll_interval_ms == 0 {
+            Some(ConfigError::ZeroPollInterval)
+        } else if !config.compensation.temperature_offset.is_finite()
+            || !config.compensation.humidity_offset.is_finite()
+            || config.compensation.humidity_offset.abs() >= 100.0
+        {
+            Some(ConfigError::InvalidCompensation)
+        } else if self
+            .adaptive_timing
+            .is_some_and(|settings| !settings.is_valid())
+        {
+            Some(ConfigError::InvalidAdaptiveTiming)
+        } else {
+            None
+        };
+        if let Some(error) = rejected {
+            return Err(BuildError {
+                i2c: self.i2c,
+                error,
+            });
+        }
+
+        Ok(AHT20 {
+            i2c: self.i2c,
+            config,
+            calibration: Calibration::Verified,
+            learner: self.adaptive_timing.map(ConversionLearner::new),
+            state: PhantomData,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Compensation, ConfigError};
+    use crate::AdaptiveTiming;
+    use crate::{Variant, AHT20, SENSOR_ADDRESS};
+    use embedded_hal_mock::eh1::i2c::Mock as I2cMock;
+
+    /// The builder with no settings matches `AHT20::new`.
+    #[test]
+    fn builder_defaults() {
+        let aht20 = AHT20::builder(I2cMock::new(&[])).build().unwrap();
+        let reference = AHT20::new(I2cMock::new(&[]), SENSOR_ADDRESS);
+        assert!(aht20.config == reference.config);
+
+        aht20.destroy().done();
+        reference.destroy().done();
+    }
+
+    /// Timing overrides apply on top of the variant, whichever is set first.
+    #[test]
+    fn builder_timing_overrides() {
+        let aht20 = AHT20::builder(I2cMock::new(&[]))
+            .measurement_delay_ms(90)
+            .variant(Variant::DHT20)
+            .build()
+            .unwrap();
+        let variant = aht20.variant();
+        assert_eq!(variant.measurement_delay_ms, 90);
+        assert_eq!(variant.power_on_delay_ms, Variant::DHT20.power_on_delay_ms);
+
+        aht20.destroy().done();
+    }
+
+    /// Invalid combinations are rejected, and the bus is handed back.
+    #[test]
+    fn builder_rejects_invalid_config() {
+        let mock = I2cMock::new(&[]);
+
+        let error = AHT20::builder(mock).address(0x80).build().err().unwrap();
+        assert_eq!(error.error(), ConfigError::InvalidAddress);
+        let mock = error.into_inner();
+
+        let error = AHT20::builder(mock)
+            .poll_interval_ms(0)
+            .build()
+            .err()
+            .unwrap();
+        assert_eq!(error.error(), ConfigError::ZeroPollInterval);
+        let mock = error.into_inner();
+
+        let error = AHT20::builder(mock)
+            .compensation(Compensation {
+                temperature_offset: f32::NAN,
+                humidity_offset: 0.0,
+            })
+            .build()
+            .err()
+            .unwrap();
+        assert_eq!(error.error(), ConfigError::InvalidCompensation);
+        let mock = error.into_inner();
+
+        let error = AHT20::builder(mock)
+            .adaptive_timing(AdaptiveTiming {
+                percentile: 0,
+                ..AdaptiveTiming::default()
+            })
+            .build()
+            .err()
+            .unwrap();
+        let (mut mock, error) = error.into_parts();
+        assert_eq!(error, ConfigError::InvalidAdaptiveTiming);
+
+        mock.done();
+    }
+}
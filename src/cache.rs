@@ -0,0 +1,257 @@
+//! Rate limiting measurements, to keep self-heating down.
+//!
+//! The datasheet recommends measuring no more than once every 2 seconds, to keep the sensor from
+//! heating itself by more than 0.1°C. When several parts of an application want readings,
+//! `AHT20Cached` makes sure they share them: a measurement requested within the minimum interval
+//! of the last one gets the cached reading back, with its age, instead of a new measurement.
+//!
+//!     # use embedded_hal_mock::eh1::delay::NoopDelay as MockDelay;
+//!     # use embedded_hal_mock::eh1::i2c::Mock as I2cMock;
+//!     # use embedded_hal_mock::eh1::i2c::Transaction;
+//!     use aht20_driver::{AHT20, DEFAULT_MIN_INTERVAL_MS, SENSOR_ADDRESS};
+//!
+//!     # let expectations = vec![
+//!     #     Transaction::read(SENSOR_ADDRESS, vec![0b0001_1000]),
+//!     #     Transaction::write(SENSOR_ADDRESS, vec![0xAC, 0x33, 0x00]),
+//!     #     Transaction::read(SENSOR_ADDRESS, vec![0b0001_1000]),
+//!     #     Transaction::read(SENSOR_ADDRESS, vec![0x1c, 0x65, 0xb4, 0x25, 0xcd, 0x26, 0xc6]),
+//!     # ];
+//!     # let mock_i2c = I2cMock::new(&expectations);
+//!     # let mut delay = MockDelay::new();
+//!     # let mut uptime_ms = || 0;
+//!     let aht20 = AHT20::new(mock_i2c, SENSOR_ADDRESS).init(&mut delay).unwrap();
+//!     let mut aht20 = aht20.into_cached(DEFAULT_MIN_INTERVAL_MS);
+//!
+//!     let first = aht20.measure(&mut delay, &mut uptime_ms).unwrap();
+//!     assert!(first.fresh);
+//!     // Too soon for another measurement, this is the same reading.
+//!     let second = aht20.measure(&mut delay, &mut uptime_ms).unwrap();
+//!     assert!(!second.fresh);
+//!     # aht20.release().destroy().done();
+
+use embedded_hal::delay::DelayNs;
+use embedded_hal::i2c::I2c;
+
+use crate::{Clock, Error, Ready, SensorReading, AHT20};
+
+/// The datasheet's recommended minimum time between measurements, in milliseconds.
+pub const DEFAULT_MIN_INTERVAL_MS: u64 = 2000;
+
+/// A reading from `AHT20Cached`, and how old it is.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CachedReading {
+    /// The temperature and humidity.
+    pub reading: SensorReading,
+    /// Milliseconds since the measurement was taken.
+    pub age_ms: u64,
+    /// Whether the measurement was taken by this call, rather than returned from the cache.
+    pub fresh: bool,
+}
+
+/// An initialized AHT20 that measures at most once per minimum interval.
+///
+/// This is returned by `AHT20::into_cached`.
+pub struct AHT20Cached<I>
+where
+    I: I2c,
+{
+    aht20: AHT20<I, Ready>,
+    min_interval_ms: u64,
+    /// The raw data of the last measurement, and when it was taken.
+    last: Option<([u8; 5], u64)>,
+}
+
+impl<I> AHT20<I, Ready>
+where
+    I: I2c,
+{
+    /// Share readings between callers, measuring at most once every `min_interval_ms`.
+    ///
+    /// `DEFAULT_MIN_INTERVAL_MS` is the datasheet's recommendation.
+    pub fn into_cached(self, min_interval_ms: u64) -> AHT20Cached<I> {
+        AHT20Cached {
+            aht20: self,
+            min_interval_ms,
+            last: None,
+        }
+    }
+}
+
+impl<I> AHT20Cached<I>
+where
+    I: I2c,
+{
+    /// Measure temperature and humidity, or return the cached reading if the last measurement was
+    /// taken less than the minimum interval ago.
+    ///
+    /// A failed measurement isn't cached, and the next call tries again.
+    pub fn measure(
+        &mut self,
+        delay: &mut impl DelayNs,
+        clock: &mut impl Clock,
+    ) -> Result<CachedReading, Error<I::Error>> {
+        let compensation = self.aht20.config.compensation;
+        self.measure_raw(delay, clock, |sensor_data| {
+            SensorReading::from_bytes(sensor_data).compensated(compensation)
+        })
+    }
+
+    /// This is identical to `measure`, except it doesn't use floating point math.
+    pub fn measure_no_fp(
+        &mut self,
+        delay: &mut impl DelayNs,
+        clock: &mut impl Clock,
+    ) -> Result<CachedReading, Error<I::Error>> {
+        self.measure_raw(delay, clock, SensorReading::from_bytes_no_fp)
+    }
+
+    /// The cached reading, without measuring. `None` if nothing has been measured yet.
+    pub fn cached(&self, clock: &mut impl Clock) -> Option<CachedReading> {
+        let compensation = self.aht20.config.compensation;
+        self.cached_raw(clock, |sensor_data| {
+            SensorReading::from_bytes(sensor_data).compensated(compensation)
+        })
+    }
+
+    /// This is identical to `cached`, except it doesn't use floating point math.
+    pub fn cached_no_fp(&self, clock: &mut impl Clock) -> Option<CachedReading> {
+        self.cached_raw(clock, SensorReading::from_bytes_no_fp)
+    }
+
+    /// Stop caching and return the driver.
+    pub fn release(self) -> AHT20<I, Ready> {
+        self.aht20
+    }
+
+    /// The cached reading, with the raw data converted by `convert`.
+    fn cached_raw(
+        &self,
+        clock: &mut impl Clock,
+        convert: impl FnOnce([u8; 5]) -> SensorReading,
+    ) -> Option<CachedReading> {
+        self.last
+            .map(|(sensor_data, measured_at_ms)| CachedReading {
+                reading: convert(sensor_data),
+                age_ms: clock.now_ms().saturating_sub(measured_at_ms),
+                fresh: false,
+            })
+    }
+
+    /// Measure if the interval has passed, and convert the raw data with `convert`.
+    fn measure_raw(
+        &mut self,
+        delay: &mut impl DelayNs,
+        clock: &mut impl Clock,
+        convert: impl FnOnce([u8; 5]) -> SensorReading,
+    ) -> Result<CachedReading, Error<I::Error>> {
+        let now_ms = clock.now_ms();
+        if let Some((sensor_data, measured_at_ms)) = self.last {
+            let age_ms = now_ms.saturating_sub(measured_at_ms);
+            if age_ms < self.min_interval_ms {
+                return Ok(CachedReading {
+                    reading: convert(sensor_data),
+                    age_ms,
+                    fresh: false,
+                });
+            }
+        }
+
+        let sensor_data = self.aht20.measure_retrying(delay)?;
+        self.last = Some((sensor_data, clock.now_ms()));
+        Ok(CachedReading {
+            reading: convert(sensor_data),
+            age_ms: 0,
+            fresh: true,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::cell::Cell;
+
+    use crate::test_support::{measurement, measurement_frame, trigger_measurement};
+    use crate::{Error, Ready, AHT20, SENSOR_ADDRESS};
+    use embedded_hal::i2c::ErrorKind;
+    use embedded_hal_mock::eh1::delay::NoopDelay as MockDelay;
+    use embedded_hal_mock::eh1::i2c::Mock as I2cMock;
+
+    /// Readings within the interval come from the cache, with their age.
+    #[test]
+    fn measure_within_interval_is_cached() {
+        let mut expectations = measurement(measurement_frame());
+        expectations.extend(measurement(measurement_frame()));
+        let mock_i2c = I2cMock::new(&expectations);
+        let mut mock_delay = MockDelay::new();
+        let now = Cell::new(0);
+        let mut clock = || now.get();
+
+        let aht20: AHT20<_, Ready> = AHT20::new(mock_i2c, SENSOR_ADDRESS).into_state();
+        let mut aht20 = aht20.into_cached(2000);
+        assert_eq!(aht20.cached(&mut clock), None);
+
+        let first = aht20.measure(&mut mock_delay, &mut clock).unwrap();
+        assert!(first.fresh);
+        assert_eq!(first.age_ms, 0);
+
+        now.set(1500);
+        let cached = aht20.measure(&mut mock_delay, &mut clock).unwrap();
+        assert!(!cached.fresh);
+        assert_eq!(cached.age_ms, 1500);
+        assert_eq!(cached.reading, first.reading);
+        assert_eq!(aht20.cached(&mut clock), Some(cached));
+
+        now.set(2000);
+        let second = aht20.measure(&mut mock_delay, &mut clock).unwrap();
+        assert!(second.fresh);
+
+        aht20.release().destroy().done();
+    }
+
+    /// The no_fp accessors convert the cached data without floating point math.
+    #[test]
+    fn cached_no_fp() {
+        let expectations = measurement(measurement_frame());
+        let mock_i2c = I2cMock::new(&expectations);
+        let mut mock_delay = MockDelay::new();
+        let now = Cell::new(0);
+        let mut clock = || now.get();
+
+        let aht20: AHT20<_, Ready> = AHT20::new(mock_i2c, SENSOR_ADDRESS).into_state();
+        let mut aht20 = aht20.into_cached(2000);
+        assert_eq!(aht20.cached_no_fp(&mut clock), None);
+
+        let first = aht20.measure_no_fp(&mut mock_delay, &mut clock).unwrap();
+        assert!(first.fresh);
+        assert_eq!(first.reading.humidity, 39.0);
+        assert_eq!(first.reading.temperature, 22.0);
+
+        now.set(500);
+        let cached = aht20.cached_no_fp(&mut clock).unwrap();
+        assert!(!cached.fresh);
+        assert_eq!(cached.age_ms, 500);
+        assert_eq!(cached.reading, first.reading);
+
+        aht20.release().destroy().done();
+    }
+
+    /// A failed measurement isn't cached, and is tried again on the next call.
+    #[test]
+    fn measure_error_not_cached() {
+        let mut expectations = vec![trigger_measurement().with_error(ErrorKind::Other)];
+        expectations.extend(measurement(measurement_frame()));
+        let mock_i2c = I2cMock::new(&expectations);
+        let mut mock_delay = MockDelay::new();
+        let mut clock = || 0;
+
+        let aht20: AHT20<_, Ready> = AHT20::new(mock_i2c, SENSOR_ADDRESS).into_state();
+        let mut aht20 = aht20.into_cached(2000);
+        assert_eq!(
+            aht20.measure(&mut mock_delay, &mut clock),
+            Err(Error::I2c(ErrorKind::Other))
+        );
+        assert!(aht20.measure(&mut mock_delay, &mut clock).unwrap().fresh);
+
+        aht20.release().destroy().done();
+    }
+}
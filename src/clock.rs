@@ -0,0 +1,30 @@
+//! Monotonic time.
+//!
+//! Some of the driver's features need to know how much time has passed, rather than just wait for
+//! a while. They take a `Clock`, which is a monotonic millisecond counter. Most HALs have a timer
+//! that can provide one, and any `FnMut() -> u64` closure is a `Clock`:
+//!
+//!     use aht20_driver::Clock;
+//!
+//!     let mut ticks = 0;
+//!     let mut clock = || {
+//!         ticks += 10;
+//!         ticks
+//!     };
+//!     assert_eq!(clock.now_ms(), 10);
+
+/// A monotonic clock counting milliseconds.
+pub trait Clock {
+    /// Milliseconds since some fixed point in the past, such as MCU boot. This must never go
+    /// backwards.
+    fn now_ms(&mut self) -> u64;
+}
+
+impl<F> Clock for F
+where
+    F: FnMut() -> u64,
+{
+    fn now_ms(&mut self) -> u64 {
+        self()
+    }
+}
@@ -0,0 +1,316 @@
+//! Thermal comfort, from a comfort zone on the psychrometric chart.
+//!
+//! ASHRAE Standard 55 draws the conditions most people find comfortable as a zone on the
+//! psychrometric chart, with temperature across and humidity ratio up. `ComfortZone` classifies a
+//! reading by where it falls relative to such a zone, and how far it is from the zone's edge:
+//!
+//!     use aht20_driver::{ComfortClass, ComfortZone, SensorReading};
+//!
+//!     let zone = ComfortZone::ashrae_55();
+//!     let office = SensorReading { humidity: 15.0, temperature: 22.0 };
+//!     let assessment = zone.assess(office);
+//!     assert_eq!(assessment.class, ComfortClass::TooDry);
+//!
+//! The default zone is only an approximation of ASHRAE 55 for typical office clothing and
+//! activity, and `ComfortZone::new` takes any other polygon.
+//!
+//! Distances are measured on the chart, where 1°C across counts the same as 1 g/kg up.
+
+use crate::SensorReading;
+
+/// A point on the psychrometric chart.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "use-defmt", derive(defmt::Format))]
+pub struct ChartPoint {
+    /// The temperature, in degrees Celsius.
+    pub temperature: f32,
+    /// The humidity ratio, in grams of water per kilogram of dry air.
+    pub humidity_ratio: f32,
+}
+
+impl ChartPoint {
+    /// The point for a reading.
+    pub fn from_reading(reading: SensorReading) -> Self {
+        ChartPoint {
+            temperature: reading.temperature,
+            humidity_ratio: reading.humidity_ratio(),
+        }
+    }
+}
+
+/// Where a reading is relative to the comfort zone.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "use-defmt", derive(defmt::Format))]
+pub enum ComfortClass {
+    /// Inside the zone.
+    Comfortable,
+    /// Outside the zone, mostly to the cold side.
+    TooCold,
+    /// Outside the zone, mostly to the warm side.
+    TooWarm,
+    /// Outside the zone, mostly below it.
+    TooDry,
+    /// Outside the zone, mostly above it.
+    TooHumid,
+}
+
+/// The result of `ComfortZone::assess`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "use-defmt", derive(defmt::Format))]
+pub struct ComfortAssessment {
+    /// Where the reading is relative to the zone.
+    pub class: ComfortClass,
+    /// How far the reading is from the edge of the zone. This is how far inside for a comfortable
+    /// reading, and how far outside otherwise.
+    pub distance: f32,
+    /// Where the reading is on the chart.
+    pub point: ChartPoint,
+}
+
+/// The error from `ComfortZone::new`, for a zone with fewer than 3 corners, a corner that isn't
+/// finite, or no area.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "use-defmt", derive(defmt::Format))]
+pub struct InvalidComfortZone;
+
+impl core::fmt::Display for InvalidComfortZone {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> Result<(), core::fmt::Error> {
+        write!(f, "invalid comfort zone")
+    }
+}
+
+impl core::error::Error for InvalidComfortZone {}
+
+/// A comfort zone, as a polygon with `N` corners on the psychrometric chart.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ComfortZone<const N: usize> {
+    corners: [ChartPoint; N],
+}
+
+impl ComfortZone<4> {
+    /// An approximation of the ASHRAE 55 comfort zone, for light office work in summer and winter
+    /// clothing, combined.
+    ///
+    /// It runs from about 20°C to 28°C in dry air, moving slightly cooler as the air gets more
+    /// humid, up to 12 g/kg. ASHRAE 55 has no lower limit on humidity, this zone ends at 4 g/kg,
+    /// below which dry skin and eyes become common.
+    pub fn ashrae_55() -> Self {
+        let point = |temperature, humidity_ratio| ChartPoint {
+            temperature,
+            humidity_ratio,
+        };
+        ComfortZone {
+            corners: [
+                point(20.5, 4.0),
+                point(28.0, 4.0),
+                point(26.5, 12.0),
+                point(20.0, 12.0),
+            ],
+        }
+    }
+}
+
+impl Default for ComfortZone<4> {
+    fn default() -> Self {
+        Self::ashrae_55()
+    }
+}
+
+impl<const N: usize> ComfortZone<N> {
+    /// A zone with these corners, in order around the edge. Returns `InvalidComfortZone` if there
+    /// are fewer than 3 corners, a corner isn't finite, or the polygon has no area.
+    pub fn new(corners: [ChartPoint; N]) -> Result<Self, InvalidComfortZone> {
+        let finite = corners
+            .iter()
+            .all(|corner| corner.temperature.is_finite() && corner.humidity_ratio.is_finite());
+        if N < 3 || !finite {
+            return Err(InvalidComfortZone);
+        }
+
+        let zone = ComfortZone { corners };
+        let twice_area: f32 = zone
+            .edges()
+            .map(|(a, b)| a.temperature * b.humidity_ratio - b.temperature * a.humidity_ratio)
+            .sum();
+        if twice_area == 0.0 {
+            return Err(InvalidComfortZone);
+        }
+        Ok(zone)
+    }
+
+    /// The corners of the zone.
+    pub fn corners(&self) -> &[ChartPoint; N] {
+        &self.corners
+    }
+
+    /// Classify a reading.
+    pub fn assess(&self, reading: SensorReading) -> ComfortAssessment {
+        self.assess_point(ChartPoint::from_reading(reading))
+    }
+
+    /// Classify a point on the chart.
+    fn assess_point(&self, point: ChartPoint) -> ComfortAssessment {
+        // The nearest point on the edge, and the distance to it.
+        let (nearest, distance) = self
+            .edges()
+            .map(|(a, b)| {
+                let nearest = nearest_on_segment(point, a, b);
+                (nearest, distance(point, nearest))
+            })
+            .fold((point, f32::INFINITY), |closest, candidate| {
+                if candidate.1 < closest.1 {
+                    candidate
+                } else {
+                    closest
+                }
+            });
+
+        let class = if self.contains(point) {
+            ComfortClass::Comfortable
+        } else {
+            let d_temperature = point.temperature - nearest.temperature;
+            let d_humidity_ratio = point.humidity_ratio - nearest.humidity_ratio;
+            if d_temperature.abs() >= d_humidity_ratio.abs() {
+                if d_temperature > 0.0 {
+                    ComfortClass::TooWarm
+                } else {
+                    ComfortClass::TooCold
+                }
+            } else if d_humidity_ratio > 0.0 {
+                ComfortClass::TooHumid
+            } else {
+                ComfortClass::TooDry
+            }
+        };
+
+        ComfortAssessment {
+            class,
+            distance,
+            point,
+        }
+    }
+
+    /// Whether `point` is inside the zone, by counting the edges a ray to its right crosses.
+    fn contains(&self, point: ChartPoint) -> bool {
+        self.edges()
+            .filter(|(a, b)| {
+                (a.humidity_ratio > point.humidity_ratio)
+                    != (b.humidity_ratio > point.humidity_ratio)
+            })
+            .filter(|(a, b)| {
+                let t = (point.humidity_ratio - a.humidity_ratio)
+                    / (b.humidity_ratio - a.humidity_ratio);
+                point.temperature < a.temperature + t * (b.temperature - a.temperature)
+            })
+            .count()
+            % 2
+            == 1
+    }
+
+    /// Each edge of the zone, as the corners at either end.
+    fn edges(&self) -> impl Iterator<Item = (ChartPoint, ChartPoint)> + '_ {
+        self.corners
+            .iter()
+            .zip(self.corners.iter().cycle().skip(1))
+            .map(|(a, b)| (*a, *b))
+    }
+}
+
+/// The point on the segment from `a` to `b` nearest to `point`.
+fn nearest_on_segment(point: ChartPoint, a: ChartPoint, b: ChartPoint) -> ChartPoint {
+    let (dx, dy) = (
+        b.temperature - a.temperature,
+        b.humidity_ratio - a.humidity_ratio,
+    );
+    let length_squared = dx * dx + dy * dy;
+    let t = if length_squared == 0.0 {
+        0.0
+    } else {
+        (((point.temperature - a.temperature) * dx
+            + (point.humidity_ratio - a.humidity_ratio) * dy)
+            / length_squared)
+            .clamp(0.0, 1.0)
+    };
+    ChartPoint {
+        temperature: a.temperature + t * dx,
+        humidity_ratio: a.humidity_ratio + t * dy,
+    }
+}
+
+/// The distance between two points on the chart.
+fn distance(a: ChartPoint, b: ChartPoint) -> f32 {
+    let (dx, dy) = (
+        a.temperature - b.temperature,
+        a.humidity_ratio - b.humidity_ratio,
+    );
+    libm::sqrtf(dx * dx + dy * dy)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ChartPoint, ComfortClass, ComfortZone, InvalidComfortZone};
+    use crate::SensorReading;
+
+    fn point(temperature: f32, humidity_ratio: f32) -> ChartPoint {
+        ChartPoint {
+            temperature,
+            humidity_ratio,
+        }
+    }
+
+    /// Typical readings get the classes you'd expect from the default zone.
+    #[test]
+    fn ashrae_55_classes() {
+        let zone = ComfortZone::default();
+        let class = |humidity, temperature| {
+            zone.assess(SensorReading {
+                humidity,
+                temperature,
+            })
+            .class
+        };
+        assert_eq!(class(40.0, 22.0), ComfortClass::Comfortable);
+        assert_eq!(class(15.0, 22.0), ComfortClass::TooDry);
+        assert_eq!(class(40.0, 30.0), ComfortClass::TooWarm);
+        assert_eq!(class(50.0, 15.0), ComfortClass::TooCold);
+        assert_eq!(class(80.0, 25.0), ComfortClass::TooHumid);
+    }
+
+    /// Distances are to the nearest edge or corner, inside and out.
+    #[test]
+    fn distance_to_edge() {
+        let square = [
+            point(0.0, 0.0),
+            point(10.0, 0.0),
+            point(10.0, 10.0),
+            point(0.0, 10.0),
+        ];
+        let zone = ComfortZone::new(square).unwrap();
+
+        let inside = zone.assess_point(point(3.0, 6.0));
+        assert_eq!(inside.class, ComfortClass::Comfortable);
+        assert_eq!(inside.distance, 3.0);
+
+        let below = zone.assess_point(point(5.0, -2.0));
+        assert_eq!(below.class, ComfortClass::TooDry);
+        assert_eq!(below.distance, 2.0);
+
+        // Past the corner, further out to the right than up.
+        let corner = zone.assess_point(point(14.0, 13.0));
+        assert_eq!(corner.class, ComfortClass::TooWarm);
+        assert_eq!(corner.distance, 5.0);
+    }
+
+    /// Zones without an area are rejected.
+    #[test]
+    fn rejects_invalid_zones() {
+        let line = [point(0.0, 0.0), point(5.0, 5.0), point(10.0, 10.0)];
+        assert_eq!(ComfortZone::new(line), Err(InvalidComfortZone));
+        assert_eq!(
+            ComfortZone::new([point(0.0, 0.0), point(5.0, 5.0)]),
+            Err(InvalidComfortZone)
+        );
+        let not_finite = [point(0.0, 0.0), point(5.0, f32::NAN), point(10.0, 0.0)];
+        assert_eq!(ComfortZone::new(not_finite), Err(InvalidComfortZone));
+    }
+}
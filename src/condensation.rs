@@ -0,0 +1,272 @@
+//! Condensation risk on a cold surface.
+//!
+//! Water condenses on a surface that is at or below the dew point of the air around it. In an
+//! enclosure, that surface is usually a metal wall or a component that is colder than the air the
+//! sensor sits in. `CondensationMonitor` compares the dew point of a reading against the
+//! temperature of that surface. The surface temperature is either measured by a second sensor or
+//! estimated as the air temperature minus a fixed offset. The difference between them, the
+//! dew-point margin, is then classified into a `CondensationRisk`:
+//!
+//!     use aht20_driver::{
+//!         CondensationMonitor, CondensationRisk, CondensationThresholds, SensorReading,
+//!         SurfaceTemperature,
+//!     };
+//!
+//!     let monitor = CondensationMonitor::new(CondensationThresholds::default()).unwrap();
+//!     let reading = SensorReading { humidity: 80.0, temperature: 20.0 };
+//!     // The cabinet wall is about 3C colder than the air inside.
+//!     let assessment = monitor.assess(reading, SurfaceTemperature::BelowAir(3.0));
+//!     // The dew point is 16.4C, just under the 17C wall.
+//!     assert_eq!(assessment.risk, CondensationRisk::High);
+//!
+//! `assess_no_fp` does the same without floating point math, using `dew_point_no_fp`, for use with
+//! `measure_no_fp` on microcontrollers without FP support.
+
+use crate::{dew_point_no_fp, SensorReading};
+
+/// The temperature of the surface that condensation would form on.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "use-defmt", derive(defmt::Format))]
+pub enum SurfaceTemperature {
+    /// The surface temperature in degrees Celsius, measured with another sensor.
+    Measured(f32),
+    /// The surface is estimated to be this many degrees Celsius colder than the air.
+    BelowAir(f32),
+}
+
+impl SurfaceTemperature {
+    /// The surface temperature, for air at `air_temperature`.
+    fn resolve(self, air_temperature: f32) -> f32 {
+        match self {
+            SurfaceTemperature::Measured(temperature) => temperature,
+            SurfaceTemperature::BelowAir(offset) => air_temperature - offset,
+        }
+    }
+}
+
+/// How likely condensation is, from least to most likely.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "use-defmt", derive(defmt::Format))]
+pub enum CondensationRisk {
+    /// The surface is at least `warning_margin` above the dew point.
+    Low,
+    /// The surface is less than `warning_margin` above the dew point.
+    Elevated,
+    /// The surface is less than `danger_margin` above the dew point.
+    High,
+    /// The surface is at or below the dew point, so water is condensing on it.
+    Condensing,
+}
+
+/// The dew-point margins, in whole degrees Celsius, below which the risk goes up.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "use-defmt", derive(defmt::Format))]
+pub struct CondensationThresholds {
+    /// Below this margin the risk is `Elevated`.
+    pub warning_margin: u8,
+    /// Below this margin the risk is `High`. Must not be more than `warning_margin`.
+    pub danger_margin: u8,
+}
+
+impl Default for CondensationThresholds {
+    fn default() -> Self {
+        CondensationThresholds {
+            warning_margin: 5,
+            danger_margin: 2,
+        }
+    }
+}
+
+/// The result of `CondensationMonitor::assess`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "use-defmt", derive(defmt::Format))]
+pub struct CondensationAssessment {
+    /// The dew point of the air, in degrees Celsius.
+    pub dew_point: f32,
+    /// The surface temperature used, in degrees Celsius.
+    pub surface_temperature: f32,
+    /// How far the surface is above the dew point, in degrees Celsius. Negative when it is below.
+    pub margin: f32,
+    /// The risk, from the margin.
+    pub risk: CondensationRisk,
+}
+
+/// The result of `CondensationMonitor::assess_no_fp`, in whole degrees Celsius.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "use-defmt", derive(defmt::Format))]
+pub struct CondensationAssessmentNoFp {
+    /// The approximate dew point of the air.
+    pub dew_point: i32,
+    /// The surface temperature used.
+    pub surface_temperature: i32,
+    /// How far the surface is above the dew point. Negative when it is below.
+    pub margin: i32,
+    /// The risk, from the margin.
+    pub risk: CondensationRisk,
+}
+
+/// The error from `CondensationMonitor::new`, for a danger margin above the warning margin.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "use-defmt", derive(defmt::Format))]
+pub struct InvalidCondensationThresholds;
+
+impl core::fmt::Display for InvalidCondensationThresholds {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> Result<(), core::fmt::Error> {
+        write!(f, "condensation danger margin above warning margin")
+    }
+}
+
+impl core::error::Error for InvalidCondensationThresholds {}
+
+/// Classifies condensation risk from readings and a surface temperature.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CondensationMonitor {
+    thresholds: CondensationThresholds,
+}
+
+impl CondensationMonitor {
+    /// Create a monitor. Returns `InvalidCondensationThresholds` if `danger_margin` is more than
+    /// `warning_margin`.
+    pub fn new(thresholds: CondensationThresholds) -> Result<Self, InvalidCondensationThresholds> {
+        if thresholds.danger_margin > thresholds.warning_margin {
+            return Err(InvalidCondensationThresholds);
+        }
+        Ok(CondensationMonitor { thresholds })
+    }
+
+    /// Assess the risk of condensation on `surface`, for air as in `reading`.
+    pub fn assess(
+        &self,
+        reading: SensorReading,
+        surface: SurfaceTemperature,
+    ) -> CondensationAssessment {
+        let dew_point = reading.dew_point();
+        let surface_temperature = surface.resolve(reading.temperature);
+        let margin = surface_temperature - dew_point;
+
+        let risk = if margin <= 0.0 {
+            CondensationRisk::Condensing
+        } else if margin < self.thresholds.danger_margin as f32 {
+            CondensationRisk::High
+        } else if margin < self.thresholds.warning_margin as f32 {
+            CondensationRisk::Elevated
+        } else {
+            CondensationRisk::Low
+        };
+
+        CondensationAssessment {
+            dew_point,
+            surface_temperature,
+            margin,
+            risk,
+        }
+    }
+
+    /// Assess the risk of condensation without floating point math, from a temperature and
+    /// surface temperature in whole degrees Celsius and a humidity in whole percent.
+    ///
+    /// This uses `dew_point_no_fp`, which is only close to the real dew point at 50% humidity and
+    /// above. Below that it overestimates the dew point, so the risk is reported higher than it
+    /// is. Use `assess` where dry air matters.
+    pub fn assess_no_fp(
+        &self,
+        temperature: i32,
+        humidity: i32,
+        surface_temperature: i32,
+    ) -> CondensationAssessmentNoFp {
+        let dew_point = dew_point_no_fp(temperature, humidity);
+        let margin = surface_temperature - dew_point;
+
+        let risk = if margin <= 0 {
+            CondensationRisk::Condensing
+        } else if margin < self.thresholds.danger_margin.into() {
+            CondensationRisk::High
+        } else if margin < self.thresholds.warning_margin.into() {
+            CondensationRisk::Elevated
+        } else {
+            CondensationRisk::Low
+        };
+
+        CondensationAssessmentNoFp {
+            dew_point,
+            surface_temperature,
+            margin,
+            risk,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        CondensationMonitor, CondensationRisk, CondensationThresholds,
+        InvalidCondensationThresholds, SurfaceTemperature,
+    };
+    use crate::SensorReading;
+
+    /// The risk goes up as the surface gets closer to the dew point.
+    #[test]
+    fn risk_levels() {
+        let monitor = CondensationMonitor::new(CondensationThresholds::default()).unwrap();
+        // A dew point of 9.3C.
+        let reading = SensorReading {
+            humidity: 50.0,
+            temperature: 20.0,
+        };
+
+        let risk = |surface| monitor.assess(reading, surface).risk;
+        assert_eq!(
+            risk(SurfaceTemperature::Measured(20.0)),
+            CondensationRisk::Low
+        );
+        assert_eq!(
+            risk(SurfaceTemperature::BelowAir(7.0)),
+            CondensationRisk::Elevated
+        );
+        assert_eq!(
+            risk(SurfaceTemperature::Measured(10.0)),
+            CondensationRisk::High
+        );
+        assert_eq!(
+            risk(SurfaceTemperature::BelowAir(12.0)),
+            CondensationRisk::Condensing
+        );
+
+        let assessment = monitor.assess(reading, SurfaceTemperature::BelowAir(5.0));
+        assert_eq!(assessment.surface_temperature, 15.0);
+        assert!((assessment.margin - 5.7).abs() < 0.1);
+    }
+
+    /// The integer version gives the same answers at high humidity.
+    #[test]
+    fn risk_levels_no_fp() {
+        let monitor = CondensationMonitor::new(CondensationThresholds::default()).unwrap();
+        // A dew point of 16C.
+        let assessment = monitor.assess_no_fp(20, 80, 20);
+        assert_eq!(assessment.dew_point, 16);
+        assert_eq!(assessment.margin, 4);
+        assert_eq!(assessment.risk, CondensationRisk::Elevated);
+        assert_eq!(monitor.assess_no_fp(20, 80, 25).risk, CondensationRisk::Low);
+        assert_eq!(
+            monitor.assess_no_fp(20, 80, 17).risk,
+            CondensationRisk::High
+        );
+        assert_eq!(
+            monitor.assess_no_fp(20, 80, 16).risk,
+            CondensationRisk::Condensing
+        );
+    }
+
+    /// A danger margin above the warning margin is rejected.
+    #[test]
+    fn rejects_invalid_thresholds() {
+        let thresholds = CondensationThresholds {
+            warning_margin: 2,
+            danger_margin: 3,
+        };
+        assert_eq!(
+            CondensationMonitor::new(thresholds),
+            Err(InvalidCondensationThresholds)
+        );
+    }
+}
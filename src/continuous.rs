@@ -0,0 +1,278 @@
+//! Continuous sampling, with one conversion always in flight.
+//!
+//! A logger that reads the sensor once a second spends most of each `measure` waiting for the
+//! conversion. `AHT20Continuous` triggers the next conversion straight after reading one, so by
+//! the time `read_latest` is called again the result is usually waiting and is read without a
+//! delay:
+//!
+//!     # use embedded_hal_mock::eh1::delay::NoopDelay as MockDelay;
+//!     # use embedded_hal_mock::eh1::i2c::Mock as I2cMock;
+//!     # use embedded_hal_mock::eh1::i2c::Transaction;
+//!     use aht20_driver::{AHT20, SENSOR_ADDRESS};
+//!
+//!     # let frame = vec![0x1c, 0x65, 0xb4, 0x25, 0xcd, 0x26, 0xc6];
+//!     # let trigger = Transaction::write(SENSOR_ADDRESS, vec![0xAC, 0x33, 0x00]);
+//!     # let expectations = vec![
+//!     #     Transaction::read(SENSOR_ADDRESS, vec![0b0001_1000]),
+//!     #     trigger.clone(),
+//!     #     Transaction::read(SENSOR_ADDRESS, vec![0b0001_1000]),
+//!     #     Transaction::read(SENSOR_ADDRESS, frame),
+//!     #     trigger,
+//!     # ];
+//!     # let mock_i2c = I2cMock::new(&expectations);
+//!     # let mut delay = MockDelay::new();
+//!     # let mut uptime_ms = || 0;
+//!     let aht20 = AHT20::new(mock_i2c, SENSOR_ADDRESS).init(&mut delay).unwrap();
+//!     // Re-trigger if a pending result is more than 5s old.
+//!     let mut aht20 = aht20.into_continuous(5000);
+//!
+//!     // The first reading waits for a conversion as usual, later ones return straight away.
+//!     let reading = aht20.read_latest(&mut delay, &mut uptime_ms).unwrap();
+//!     # aht20.stop().destroy().done();
+//!
+//! A pending result older than the staleness limit isn't used, as it no longer says much about
+//! the current conditions. A new conversion is triggered and waited for instead.
+
+use embedded_hal::delay::DelayNs;
+use embedded_hal::i2c::I2c;
+
+use crate::{Calibration, Clock, Error, Ready, SensorReading, AHT20};
+
+/// An initialized AHT20 that keeps a conversion in flight between readings.
+///
+/// This is returned by `AHT20::into_continuous`, and `stop` turns it back into a normal driver.
+pub struct AHT20Continuous<I>
+where
+    I: I2c,
+{
+    aht20: AHT20<I, Ready>,
+    staleness_limit_ms: u64,
+    /// When the pending conversion was triggered, if there is one.
+    triggered_at_ms: Option<u64>,
+}
+
+impl<I> AHT20<I, Ready>
+where
+    I: I2c,
+{
+    /// Switch to continuous sampling. Pending results older than `staleness_limit_ms` are thrown
+    /// away rather than returned.
+    ///
+    /// This doesn't talk to the sensor, the first `read_latest` triggers the first conversion.
+    pub fn into_continuous(self, staleness_limit_ms: u64) -> AHT20Continuous<I> {
+        AHT20Continuous {
+            aht20: self,
+            staleness_limit_ms,
+            triggered_at_ms: None,
+        }
+    }
+}
+
+impl<I> AHT20Continuous<I>
+where
+    I: I2c,
+{
+    /// Read the pending conversion, and trigger the next one.
+    ///
+    /// This only waits if the pending conversion hasn't had time to finish. If there is no pending
+    /// conversion, or it is older than the staleness limit, a new one is triggered and waited for
+    /// like `measure` does.
+    pub fn read_latest(
+        &mut self,
+        delay: &mut impl DelayNs,
+        clock: &mut impl Clock,
+    ) -> Result<SensorReading, Error<I::Error>> {
+        let sensor_data = self.read_latest_raw(delay, clock)?;
+        Ok(SensorReading::from_bytes(sensor_data).compensated(self.aht20.config.compensation))
+    }
+
+    /// This is identical to `read_latest`, except it doesn't use floating point math.
+    pub fn read_latest_no_fp(
+        &mut self,
+        delay: &mut impl DelayNs,
+        clock: &mut impl Clock,
+    ) -> Result<SensorReading, Error<I::Error>> {
+        let sensor_data = self.read_latest_raw(delay, clock)?;
+        Ok(SensorReading::from_bytes_no_fp(sensor_data))
+    }
+
+    /// How long ago the pending conversion was triggered, or `None` if there isn't one.
+    pub fn pending_age_ms(&self, clock: &mut impl Clock) -> Option<u64> {
+        self.triggered_at_ms
+            .map(|triggered_at_ms| clock.now_ms().saturating_sub(triggered_at_ms))
+    }
+
+    /// Stop continuous sampling and return the driver.
+    ///
+    /// A conversion may still be in flight, it is left to finish and its result is never read.
+    pub fn stop(self) -> AHT20<I, Ready> {
+        self.aht20
+    }
+
+    /// Get the 5 raw data bytes of the latest result, and trigger the next conversion.
+    fn read_latest_raw(
+        &mut self,
+        delay: &mut impl DelayNs,
+        clock: &mut impl Clock,
+    ) -> Result<[u8; 5], Error<I::Error>> {
+        if self.aht20.calibration == Calibration::Lost {
+            return Err(Error::Uncalibrated);
+        }
+
+        let pending_age_ms = self
+            .pending_age_ms(clock)
+            .filter(|age_ms| *age_ms <= self.staleness_limit_ms);
+        self.triggered_at_ms = None;
+
+        let result = match pending_age_ms {
+            Some(age_ms) => {
+                let wait_ms = u64::from(self.aht20.measurement_wait_ms()).saturating_sub(age_ms);
+                if wait_ms > 0 {
+                    // The wait is at most measurement_wait_ms, which is a u32.
+                    delay.delay_ms(wait_ms as u32);
+                }
+                self.aht20
+                    .read_triggered(delay)
+                    .map(|(sensor_data, _)| sensor_data)
+            }
+            None => {
+                #[cfg(feature = "use-defmt")]
+                defmt::debug!("read_latest: no recent conversion, triggering one.");
+                self.aht20.measure_retrying(delay)
+            }
+        };
+        let sensor_data = match result {
+            // A bad pending result is retried with fresh conversions, like measure does.
+            Err(Error::InvalidCrc) if pending_age_ms.is_some() => {
+                self.aht20.measure_retrying(delay)?
+            }
+            Err(Error::UnexpectedBusy) if pending_age_ms.is_some() => {
+                self.aht20.distrust_learned_wait();
+                self.aht20.measure_retrying(delay)?
+            }
+            other => other?,
+        };
+
+        // If this fails the reading is still good. The next call will find no pending conversion
+        // and trigger one, and report the bus error if it persists.
+        if self.aht20.send_trigger_measurement().is_ok() {
+            self.triggered_at_ms = Some(clock.now_ms());
+        }
+
+        Ok(sensor_data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::cell::Cell;
+
+    use crate::test_support::{measurement_frame, read_measurement, trigger_measurement};
+    use crate::{AdaptiveTiming, Ready, AHT20, SENSOR_ADDRESS};
+    use embedded_hal_mock::eh1::delay::{CheckedDelay, Transaction as DelayTransaction};
+    use embedded_hal_mock::eh1::i2c::Mock as I2cMock;
+    use embedded_hal_mock::eh1::i2c::Transaction;
+
+    /// The first reading waits for a conversion, later ones read the pending conversion.
+    #[test]
+    fn read_latest_uses_pending_conversion() {
+        let mut expectations = vec![trigger_measurement()];
+        expectations.extend(read_measurement(measurement_frame()));
+        expectations.push(trigger_measurement());
+        // A second later, the pending result is read straight away.
+        expectations.extend(read_measurement(measurement_frame()));
+        expectations.push(trigger_measurement());
+        // 30ms later, the rest of the conversion time is waited for.
+        expectations.extend(read_measurement(measurement_frame()));
+        expectations.push(trigger_measurement());
+        let mock_i2c = I2cMock::new(&expectations);
+        let mut mock_delay = CheckedDelay::new(&[
+            DelayTransaction::delay_ms(80),
+            DelayTransaction::delay_ms(50),
+        ]);
+        let now = Cell::new(0);
+        let mut clock = || now.get();
+
+        let aht20: AHT20<_, Ready> = AHT20::new(mock_i2c, SENSOR_ADDRESS).into_state();
+        let mut aht20 = aht20.into_continuous(5000);
+        let first = aht20.read_latest(&mut mock_delay, &mut clock).unwrap();
+        now.set(1000);
+        assert_eq!(aht20.pending_age_ms(&mut clock), Some(1000));
+        let second = aht20.read_latest(&mut mock_delay, &mut clock).unwrap();
+        assert_eq!(first, second);
+        now.set(1030);
+        aht20.read_latest(&mut mock_delay, &mut clock).unwrap();
+
+        aht20.stop().destroy().done();
+        mock_delay.done();
+    }
+
+    /// A pending conversion older than the staleness limit is replaced with a new one.
+    #[test]
+    fn read_latest_retriggers_stale_conversion() {
+        let mut expectations = vec![trigger_measurement()];
+        expectations.extend(read_measurement(measurement_frame()));
+        expectations.push(trigger_measurement());
+        expectations.push(trigger_measurement());
+        expectations.extend(read_measurement(measurement_frame()));
+        expectations.push(trigger_measurement());
+        let mock_i2c = I2cMock::new(&expectations);
+        let mut mock_delay = CheckedDelay::new(&[
+            DelayTransaction::delay_ms(80),
+            DelayTransaction::delay_ms(80),
+        ]);
+        let now = Cell::new(0);
+        let mut clock = || now.get();
+
+        let aht20: AHT20<_, Ready> = AHT20::new(mock_i2c, SENSOR_ADDRESS).into_state();
+        let mut aht20 = aht20.into_continuous(5000);
+        aht20.read_latest(&mut mock_delay, &mut clock).unwrap();
+        now.set(10_000);
+        aht20.read_latest(&mut mock_delay, &mut clock).unwrap();
+        assert_eq!(aht20.pending_age_ms(&mut clock), Some(0));
+
+        aht20.stop().destroy().done();
+        mock_delay.done();
+    }
+
+    /// A pending result that contradicts its ready status makes adaptive timing fall back to the
+    /// full wait for the retry.
+    #[test]
+    fn read_latest_falls_back_on_unexpected_busy() {
+        let mut expectations = vec![trigger_measurement()];
+        expectations.extend(read_measurement(measurement_frame()));
+        expectations.push(trigger_measurement());
+        // The pending frame is busy, despite a ready status.
+        expectations.push(Transaction::read(SENSOR_ADDRESS, vec![0b0001_1000]));
+        expectations.push(Transaction::read(
+            SENSOR_ADDRESS,
+            vec![0x9c, 0x65, 0xb4, 0x25, 0xcd, 0x26, 0x2a],
+        ));
+        expectations.push(trigger_measurement());
+        expectations.extend(read_measurement(measurement_frame()));
+        expectations.push(trigger_measurement());
+        let mock_i2c = I2cMock::new(&expectations);
+        let mut mock_delay = CheckedDelay::new(&[
+            DelayTransaction::delay_ms(30),
+            DelayTransaction::delay_ms(20),
+            DelayTransaction::delay_ms(80),
+        ]);
+        let now = Cell::new(0);
+        let mut clock = || now.get();
+
+        let aht20 = AHT20::builder(mock_i2c)
+            .adaptive_timing(AdaptiveTiming::default())
+            .build()
+            .unwrap();
+        let aht20: AHT20<_, Ready> = aht20.into_state();
+        let mut aht20 = aht20.into_continuous(5000);
+        aht20.read_latest(&mut mock_delay, &mut clock).unwrap();
+        now.set(10);
+        aht20.read_latest(&mut mock_delay, &mut clock).unwrap();
+
+        let aht20 = aht20.stop();
+        assert_eq!(aht20.measurement_wait_ms(), 80);
+        aht20.destroy().done();
+        mock_delay.done();
+    }
+}
@@ -0,0 +1,487 @@
+//! On/off control of a heater, fan or dehumidifier.
+//!
+//! `AHT20Controller` measures, and switches an `OutputPin` to keep temperature, humidity or dew
+//! point near a set point. The output switches on when the value leaves the deadband on one side,
+//! and off when it leaves on the other, so it doesn't chatter around the set point. The minimum
+//! on and off times hold the output in its state for at least that long after switching, to
+//! protect compressors and relays.
+//!
+//! If a measurement fails, the output is driven to the fail-safe state at once, regardless of the
+//! minimum times, and the error is returned. A driver without a retry budget is given one of
+//! `CONTROL_RETRY_BUDGET`, so a sensor that keeps failing its CRC check can't hold the output in
+//! its last state forever. Set one with `AHT20Builder::retry_budget` to choose another.
+//!
+//!     # use embedded_hal_mock::eh1::delay::NoopDelay as MockDelay;
+//!     # use embedded_hal_mock::eh1::digital::{Mock as PinMock, State, Transaction as PinTransaction};
+//!     # use embedded_hal_mock::eh1::i2c::Mock as I2cMock;
+//!     # use embedded_hal_mock::eh1::i2c::Transaction;
+//!     use aht20_driver::{
+//!         ControlDirection, ControlSettings, HysteresisControl, Quantity, AHT20, SENSOR_ADDRESS,
+//!     };
+//!     use embedded_hal::digital::PinState;
+//!
+//!     # let expectations = vec![
+//!     #     Transaction::read(SENSOR_ADDRESS, vec![0b0001_1000]),
+//!     #     Transaction::write(SENSOR_ADDRESS, vec![0xAC, 0x33, 0x00]),
+//!     #     Transaction::read(SENSOR_ADDRESS, vec![0b0001_1000]),
+//!     #     Transaction::read(SENSOR_ADDRESS, vec![0x1c, 0x65, 0xb4, 0x25, 0xcd, 0x26, 0xc6]),
+//!     # ];
+//!     # let mock_i2c = I2cMock::new(&expectations);
+//!     # let heater_pin = PinMock::new(&[PinTransaction::set(State::Low)]);
+//!     # let mut delay = MockDelay::new();
+//!     # let mut uptime_ms = || 0;
+//!     let aht20 = AHT20::new(mock_i2c, SENSOR_ADDRESS).init(&mut delay).unwrap();
+//!     let control = HysteresisControl::new(ControlSettings {
+//!         quantity: Quantity::Temperature,
+//!         direction: ControlDirection::Raise,
+//!         set_point: 21.0,
+//!         deadband: 1.0,
+//!         min_on_ms: 60_000,
+//!         min_off_ms: 60_000,
+//!         fail_safe: PinState::Low,
+//!     })
+//!     .unwrap();
+//!     let mut thermostat = aht20.into_controller(heater_pin, control);
+//!
+//!     // It's 22.5C, so the heater stays off.
+//!     let status = thermostat.update(&mut delay, &mut uptime_ms).unwrap();
+//!     assert_eq!(status.output, PinState::Low);
+//!     # let (aht20, mut pin) = thermostat.release();
+//!     # aht20.destroy().done();
+//!     # pin.done();
+
+use embedded_hal::delay::DelayNs;
+use embedded_hal::digital::{OutputPin, PinState};
+use embedded_hal::i2c::I2c;
+
+use crate::{Clock, Error, Quantity, Ready, SensorReading, AHT20};
+
+/// The retry budget `AHT20::into_controller` gives a driver that doesn't have one.
+pub const CONTROL_RETRY_BUDGET: u8 = 3;
+
+/// Which way switching the output on moves the controlled value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "use-defmt", derive(defmt::Format))]
+pub enum ControlDirection {
+    /// The output raises the value, like a heater or humidifier. It switches on below the
+    /// deadband.
+    Raise,
+    /// The output lowers the value, like a cooler, fan or dehumidifier. It switches on above the
+    /// deadband.
+    Lower,
+}
+
+/// Settings for `AHT20Controller`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ControlSettings {
+    /// What to control.
+    pub quantity: Quantity,
+    /// Whether the output raises or lowers the value.
+    pub direction: ControlDirection,
+    /// The value to hold, in the quantity's units.
+    pub set_point: f32,
+    /// The width of the band around the set point in which the output doesn't switch. The output
+    /// switches at `set_point ± deadband / 2`.
+    pub deadband: f32,
+    /// How long the output stays on after switching on, at least, in milliseconds.
+    pub min_on_ms: u64,
+    /// How long the output stays off after switching off, at least, in milliseconds.
+    pub min_off_ms: u64,
+    /// The output when a measurement fails. `PinState::High` is on.
+    pub fail_safe: PinState,
+}
+
+impl ControlSettings {
+    /// Check that the set point is finite, and the deadband is finite and not negative.
+    fn is_valid(&self) -> bool {
+        self.set_point.is_finite() && self.deadband.is_finite() && self.deadband >= 0.0
+    }
+}
+
+/// The error from `HysteresisControl::new`, for a set point that isn't finite, or a deadband that
+/// is negative or not finite.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "use-defmt", derive(defmt::Format))]
+pub struct InvalidControlSettings;
+
+impl core::fmt::Display for InvalidControlSettings {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> Result<(), core::fmt::Error> {
+        write!(f, "invalid control settings")
+    }
+}
+
+impl core::error::Error for InvalidControlSettings {}
+
+/// On/off control with a deadband and minimum on and off times, for `AHT20::into_controller`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HysteresisControl {
+    settings: ControlSettings,
+    /// The state the output was last set to. `None` before it has been set, or after setting it
+    /// failed.
+    output: Option<PinState>,
+    /// When the output last switched.
+    switched_at_ms: Option<u64>,
+}
+
+impl HysteresisControl {
+    /// Create the control, with the output not set yet. Returns `InvalidControlSettings` if the set
+    /// point isn't finite, or the deadband is negative or not finite.
+    pub fn new(settings: ControlSettings) -> Result<Self, InvalidControlSettings> {
+        if !settings.is_valid() {
+            return Err(InvalidControlSettings);
+        }
+        Ok(HysteresisControl {
+            settings,
+            output: None,
+            switched_at_ms: None,
+        })
+    }
+
+    /// The settings.
+    pub fn settings(&self) -> &ControlSettings {
+        &self.settings
+    }
+
+    /// The state the output was last set to, `None` before it has been set.
+    pub fn output(&self) -> Option<PinState> {
+        self.output
+    }
+
+    /// The output wanted for `value` at `now_ms`, and whether a switch to it is being held by a
+    /// minimum on or off time. An output that hasn't been set is off.
+    fn decide(&self, value: f32, now_ms: u64) -> (PinState, bool) {
+        let settings = &self.settings;
+        let below = value < settings.set_point - settings.deadband / 2.0;
+        let above = value > settings.set_point + settings.deadband / 2.0;
+        let (switch_on, switch_off) = match settings.direction {
+            ControlDirection::Raise => (below, above),
+            ControlDirection::Lower => (above, below),
+        };
+
+        let current = self.output.unwrap_or(PinState::Low);
+        let wanted = if switch_on {
+            PinState::High
+        } else if switch_off {
+            PinState::Low
+        } else {
+            current
+        };
+
+        let held = match (self.output, self.switched_at_ms) {
+            (Some(current), Some(switched_at_ms)) if wanted != current => {
+                let min_ms = match current {
+                    PinState::High => settings.min_on_ms,
+                    PinState::Low => settings.min_off_ms,
+                };
+                now_ms.saturating_sub(switched_at_ms) < min_ms
+            }
+            _ => false,
+        };
+        (wanted, held)
+    }
+}
+
+/// The result of one `AHT20Controller::update`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ControlStatus {
+    /// The reading that was measured.
+    pub reading: SensorReading,
+    /// The output after the update. `PinState::High` is on.
+    pub output: PinState,
+    /// Whether the output should have switched, but is being held by a minimum on or off time.
+    pub held: bool,
+}
+
+/// Errors from `AHT20Controller::update`.
+#[derive(Debug, PartialEq)]
+pub enum ControllerError<E, P> {
+    /// The measurement failed. The output was switched to the fail-safe state.
+    Sensor(Error<E>),
+    /// Setting the output pin failed.
+    Pin(P),
+}
+
+impl<E, P> core::fmt::Display for ControllerError<E, P> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> Result<(), core::fmt::Error> {
+        match self {
+            ControllerError::Sensor(error) => write!(f, "sensor error: {}", error),
+            ControllerError::Pin(_e) => write!(f, "output pin error"),
+        }
+    }
+}
+
+impl<E, P> core::error::Error for ControllerError<E, P>
+where
+    E: core::fmt::Debug,
+    P: core::fmt::Debug,
+{
+}
+
+/// An initialized AHT20 switching an output pin to hold a set point.
+///
+/// This is returned by `AHT20::into_controller`.
+pub struct AHT20Controller<I, P>
+where
+    I: I2c,
+    P: OutputPin,
+{
+    aht20: AHT20<I, Ready>,
+    pin: P,
+    control: HysteresisControl,
+}
+
+impl<I> AHT20<I, Ready>
+where
+    I: I2c,
+{
+    /// Switch `pin` from measurements, with `control`.
+    ///
+    /// The pin isn't touched until the first `update`. If the driver retries measurements until
+    /// they succeed, its retry budget is set to `CONTROL_RETRY_BUDGET`, so that a failing sensor
+    /// reaches the fail-safe state. The budget stays set on the driver `release` returns.
+    pub fn into_controller<P>(mut self, pin: P, control: HysteresisControl) -> AHT20Controller<I, P>
+    where
+        P: OutputPin,
+    {
+        self.config.retry_budget.get_or_insert(CONTROL_RETRY_BUDGET);
+        AHT20Controller {
+            aht20: self,
+            pin,
+            control,
+        }
+    }
+}
+
+impl<I, P> AHT20Controller<I, P>
+where
+    I: I2c,
+    P: OutputPin,
+{
+    /// Measure, and switch the output if needed. Call this periodically.
+    ///
+    /// The first update sets the pin, to off if the value is within the deadband.
+    pub fn update(
+        &mut self,
+        delay: &mut impl DelayNs,
+        clock: &mut impl Clock,
+    ) -> Result<ControlStatus, ControllerError<I::Error, P::Error>> {
+        let reading = match self.aht20.measure(delay) {
+            Ok(reading) => reading,
+            Err(error) => {
+                #[cfg(feature = "use-defmt")]
+                defmt::warn!("AHT20Controller: measurement failed, switching to fail-safe.");
+                let fail_safe = self.control.settings.fail_safe;
+                self.drive(fail_safe, clock.now_ms())
+                    .map_err(ControllerError::Pin)?;
+                return Err(ControllerError::Sensor(error));
+            }
+        };
+
+        let now_ms = clock.now_ms();
+        let value = reading.get(self.control.settings.quantity);
+        let (wanted, held) = self.control.decide(value, now_ms);
+        if !held {
+            self.drive(wanted, now_ms).map_err(ControllerError::Pin)?;
+        }
+
+        Ok(ControlStatus {
+            reading,
+            output: self.control.output.unwrap_or(wanted),
+            held,
+        })
+    }
+
+    /// The control, with its settings and the state the output was last set to.
+    pub fn control(&self) -> &HysteresisControl {
+        &self.control
+    }
+
+    /// Stop controlling and return the driver and the pin, in whatever state it was left.
+    pub fn release(self) -> (AHT20<I, Ready>, P) {
+        (self.aht20, self.pin)
+    }
+
+    /// Set the pin to `state` if it isn't already, and note when it switched.
+    fn drive(&mut self, state: PinState, now_ms: u64) -> Result<(), P::Error> {
+        if self.control.output == Some(state) {
+            return Ok(());
+        }
+        if let Err(error) = self.pin.set_state(state) {
+            // We no longer know what state the pin is in, so it's set again next time.
+            self.control.output = None;
+            return Err(error);
+        }
+        self.control.output = Some(state);
+        self.control.switched_at_ms = Some(now_ms);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::cell::Cell;
+
+    use super::{
+        ControlDirection, ControlSettings, ControllerError, HysteresisControl,
+        InvalidControlSettings, CONTROL_RETRY_BUDGET,
+    };
+    use crate::test_support::{self, raw_frame, trigger_measurement};
+    use crate::{Error, Quantity, Ready, AHT20, SENSOR_ADDRESS};
+    use embedded_hal::digital::PinState;
+    use embedded_hal::i2c::ErrorKind;
+    use embedded_hal_mock::eh1::delay::NoopDelay as MockDelay;
+    use embedded_hal_mock::eh1::digital::{Mock as PinMock, State, Transaction as PinTransaction};
+    use embedded_hal_mock::eh1::i2c::Mock as I2cMock;
+    use embedded_hal_mock::eh1::i2c::Transaction;
+
+    /// A simulated measurement of `temperature` and 50% humidity.
+    fn measurement(temperature: f32) -> Vec<Transaction> {
+        let temperature = ((temperature + 50.0) / 200.0 * (1 << 20) as f32) as u32;
+        test_support::measurement(raw_frame(1 << 19, temperature))
+    }
+
+    fn thermostat() -> ControlSettings {
+        ControlSettings {
+            quantity: Quantity::Temperature,
+            direction: ControlDirection::Raise,
+            set_point: 20.0,
+            deadband: 1.0,
+            min_on_ms: 60_000,
+            min_off_ms: 30_000,
+            fail_safe: PinState::Low,
+        }
+    }
+
+    /// The output switches outside the deadband, and is held for the minimum times.
+    #[test]
+    fn switches_with_deadband_and_minimum_times() {
+        let mut expectations = Vec::new();
+        for temperature in [20.0, 19.0, 21.0, 21.0, 19.0, 19.0] {
+            expectations.extend(measurement(temperature));
+        }
+        let mock_i2c = I2cMock::new(&expectations);
+        let mock_pin = PinMock::new(&[
+            PinTransaction::set(State::Low),
+            PinTransaction::set(State::High),
+            PinTransaction::set(State::Low),
+            PinTransaction::set(State::High),
+        ]);
+        let mut mock_delay = MockDelay::new();
+        let now = Cell::new(0);
+        let mut clock = || now.get();
+
+        let aht20: AHT20<_, Ready> = AHT20::new(mock_i2c, SENSOR_ADDRESS).into_state();
+        let control = HysteresisControl::new(thermostat()).unwrap();
+        let mut controller = aht20.into_controller(mock_pin, control);
+        assert_eq!(controller.control().output(), None);
+
+        // Within the deadband, the first update switches off.
+        let status = controller.update(&mut mock_delay, &mut clock).unwrap();
+        assert_eq!(status.output, PinState::Low);
+        // Off for long enough, so the heater switches on at once.
+        now.set(30_000);
+        let status = controller.update(&mut mock_delay, &mut clock).unwrap();
+        assert_eq!((status.output, status.held), (PinState::High, false));
+        // Too warm, but the heater has to stay on for a minute.
+        now.set(60_000);
+        let status = controller.update(&mut mock_delay, &mut clock).unwrap();
+        assert_eq!((status.output, status.held), (PinState::High, true));
+        now.set(90_000);
+        let status = controller.update(&mut mock_delay, &mut clock).unwrap();
+        assert_eq!((status.output, status.held), (PinState::Low, false));
+        // Too cold again, but the heater has to stay off for 30 seconds.
+        now.set(100_000);
+        let status = controller.update(&mut mock_delay, &mut clock).unwrap();
+        assert_eq!((status.output, status.held), (PinState::Low, true));
+        now.set(120_000);
+        let status = controller.update(&mut mock_delay, &mut clock).unwrap();
+        assert_eq!(status.output, PinState::High);
+
+        let (aht20, mut pin) = controller.release();
+        aht20.destroy().done();
+        pin.done();
+    }
+
+    /// A failed measurement switches to the fail-safe state straight away.
+    #[test]
+    fn fail_safe_on_sensor_error() {
+        let mut expectations = measurement(25.0);
+        expectations.push(trigger_measurement().with_error(ErrorKind::Other));
+        let mock_i2c = I2cMock::new(&expectations);
+        let mock_pin = PinMock::new(&[
+            PinTransaction::set(State::High),
+            PinTransaction::set(State::Low),
+        ]);
+        let mut mock_delay = MockDelay::new();
+        let mut clock = || 0;
+
+        let cooler = ControlSettings {
+            quantity: Quantity::Temperature,
+            direction: ControlDirection::Lower,
+            ..thermostat()
+        };
+        let aht20: AHT20<_, Ready> = AHT20::new(mock_i2c, SENSOR_ADDRESS).into_state();
+        let control = HysteresisControl::new(cooler).unwrap();
+        let mut controller = aht20.into_controller(mock_pin, control);
+
+        let status = controller.update(&mut mock_delay, &mut clock).unwrap();
+        assert_eq!(status.output, PinState::High);
+        assert_eq!(
+            controller.update(&mut mock_delay, &mut clock),
+            Err(ControllerError::Sensor(Error::I2c(ErrorKind::Other)))
+        );
+        assert_eq!(controller.control().output(), Some(PinState::Low));
+
+        let (aht20, mut pin) = controller.release();
+        aht20.destroy().done();
+        pin.done();
+    }
+
+    /// A sensor that keeps failing its CRC check reaches the fail-safe state once the retry budget
+    /// the controller sets is spent, rather than retrying forever.
+    #[test]
+    fn fail_safe_on_repeated_crc_errors() {
+        let mut expectations = measurement(15.0);
+        let mut bad_frame = raw_frame(1 << 19, 1 << 19);
+        bad_frame[6] ^= 0b0000_0001;
+        for _ in 0..=CONTROL_RETRY_BUDGET {
+            expectations.extend(test_support::measurement(bad_frame.clone()));
+        }
+        let mock_i2c = I2cMock::new(&expectations);
+        let mock_pin = PinMock::new(&[
+            PinTransaction::set(State::High),
+            PinTransaction::set(State::Low),
+        ]);
+        let mut mock_delay = MockDelay::new();
+        let mut clock = || 0;
+
+        let aht20: AHT20<_, Ready> = AHT20::new(mock_i2c, SENSOR_ADDRESS).into_state();
+        let control = HysteresisControl::new(thermostat()).unwrap();
+        let mut controller = aht20.into_controller(mock_pin, control);
+
+        let status = controller.update(&mut mock_delay, &mut clock).unwrap();
+        assert_eq!(status.output, PinState::High);
+        assert_eq!(
+            controller.update(&mut mock_delay, &mut clock),
+            Err(ControllerError::Sensor(Error::InvalidCrc))
+        );
+        assert_eq!(controller.control().output(), Some(PinState::Low));
+
+        let (aht20, mut pin) = controller.release();
+        aht20.destroy().done();
+        pin.done();
+    }
+
+    /// A negative deadband is rejected.
+    #[test]
+    fn rejects_invalid_settings() {
+        let settings = ControlSettings {
+            deadband: -1.0,
+            ..thermostat()
+        };
+        assert_eq!(
+            HysteresisControl::new(settings),
+            Err(InvalidControlSettings)
+        );
+    }
+}
@@ -0,0 +1,391 @@
+//! Heating, cooling and growing degree days.
+//!
+//! Degree days measure how far, and for how long, the temperature was below or above a base
+//! temperature. Heating degree days count the time below the base, and cooling degree days the
+//! time above it. Growing degree days are cooling degree days against a crop's base temperature,
+//! such as 10°C for maize.
+//!
+//! `DegreeDays` works them out from timestamped readings, a day at a time, in one of two ways:
+//!
+//! * `DegreeDayMethod::Integration` adds up the difference from the base over time, taking each
+//!   reading's temperature to hold until the next one.
+//! * `DegreeDayMethod::MinMax` uses the difference between the base and the mean of the day's
+//!   lowest and highest temperatures, as most published degree day figures do.
+//!
+//! A day ends when a reading for a later day comes in, or when `close_due` is called after its
+//! end, and its totals are returned and added to the running totals:
+//!
+//!     use aht20_driver::{DegreeDayMethod, DegreeDaySettings, DegreeDays, SensorReading};
+//!
+//!     let mut degree_days = DegreeDays::new(DegreeDaySettings {
+//!         base_temperature: 15.5,
+//!         method: DegreeDayMethod::MinMax,
+//!         day_start_ms: 0,
+//!     })
+//!     .unwrap();
+//!     let hour_ms = 3_600_000;
+//!     degree_days.update(SensorReading { humidity: 50.0, temperature: 4.0 }, 4 * hour_ms);
+//!     degree_days.update(SensorReading { humidity: 50.0, temperature: 12.0 }, 14 * hour_ms);
+//!
+//!     let day = degree_days.close_due(24 * hour_ms).unwrap();
+//!     // 15.5C less the mean of 4C and 12C.
+//!     assert_eq!(day.heating, 7.5);
+//!     assert_eq!(degree_days.totals().heating, 7.5);
+//!
+//! Days are 24 hours long, and start `day_start_ms` after each multiple of 24 hours on the clock
+//! used for the timestamps. Days without any readings are skipped, and the time in them isn't
+//! counted.
+
+use crate::SensorReading;
+
+/// Milliseconds in a day.
+const DAY_MS: u64 = 24 * 60 * 60 * 1000;
+
+/// How degree days are worked out from the readings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "use-defmt", derive(defmt::Format))]
+pub enum DegreeDayMethod {
+    /// Add up the difference from the base over time.
+    Integration,
+    /// Compare the mean of the day's lowest and highest temperatures with the base.
+    MinMax,
+}
+
+/// Settings for `DegreeDays`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "use-defmt", derive(defmt::Format))]
+pub struct DegreeDaySettings {
+    /// The base temperature, in degrees Celsius.
+    pub base_temperature: f32,
+    /// How the degree days are worked out.
+    pub method: DegreeDayMethod,
+    /// When days start, in milliseconds after each multiple of 24 hours on the clock. For
+    /// example, 9 hours for a 9am day with timestamps from midnight. Must be less than 24 hours.
+    pub day_start_ms: u64,
+}
+
+/// The degree days of one day.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "use-defmt", derive(defmt::Format))]
+pub struct DailyDegreeDays {
+    /// When the day ends, in milliseconds.
+    pub end_ms: u64,
+    /// Heating degree days, for the time below the base.
+    pub heating: f32,
+    /// Cooling degree days, for the time above the base.
+    pub cooling: f32,
+    /// The lowest temperature of the day, in degrees Celsius.
+    pub min_temperature: f32,
+    /// The highest temperature of the day, in degrees Celsius.
+    pub max_temperature: f32,
+}
+
+/// Degree days added up over every closed day.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[cfg_attr(feature = "use-defmt", derive(defmt::Format))]
+pub struct DegreeDayTotals {
+    /// Heating degree days.
+    pub heating: f32,
+    /// Cooling degree days.
+    pub cooling: f32,
+    /// How many days have been added.
+    pub days: u32,
+}
+
+/// The day in progress.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Day {
+    end_ms: u64,
+    /// The integrated degree days so far.
+    heating: f32,
+    cooling: f32,
+    min_temperature: f32,
+    max_temperature: f32,
+}
+
+/// The error from `DegreeDays::new`, for a base temperature that isn't finite, or a day start that
+/// isn't less than 24 hours.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "use-defmt", derive(defmt::Format))]
+pub struct InvalidDegreeDaySettings;
+
+impl core::fmt::Display for InvalidDegreeDaySettings {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> Result<(), core::fmt::Error> {
+        write!(f, "invalid degree day settings")
+    }
+}
+
+impl core::error::Error for InvalidDegreeDaySettings {}
+
+/// Accumulates degree days from timestamped readings.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DegreeDays {
+    settings: DegreeDaySettings,
+    open: Option<Day>,
+    /// The previous temperature, and when it was read.
+    last: Option<(f32, u64)>,
+    totals: DegreeDayTotals,
+}
+
+impl DegreeDays {
+    /// Create an accumulator with no days counted. Returns `InvalidDegreeDaySettings` if the base
+    /// temperature isn't finite, or the day start isn't less than 24 hours.
+    pub fn new(settings: DegreeDaySettings) -> Result<Self, InvalidDegreeDaySettings> {
+        if !settings.base_temperature.is_finite() || settings.day_start_ms >= DAY_MS {
+            return Err(InvalidDegreeDaySettings);
+        }
+        Ok(DegreeDays {
+            settings,
+            open: None,
+            last: None,
+            totals: DegreeDayTotals::default(),
+        })
+    }
+
+    /// Add a temperature reading taken at `timestamp_ms`.
+    ///
+    /// If the reading belongs to a later day than the open one, the open day is closed and its
+    /// degree days returned. A reading older than the previous one is ignored.
+    pub fn update(&mut self, reading: SensorReading, timestamp_ms: u64) -> Option<DailyDegreeDays> {
+        if self.last.is_some_and(|(_, last_ms)| timestamp_ms < last_ms) {
+            return None;
+        }
+
+        let end_ms = self.day_end_ms(timestamp_ms);
+        let closed = match self.open {
+            Some(day) if end_ms > day.end_ms => self.close(day.end_ms),
+            _ => None,
+        };
+
+        // The previous temperature carries on into a new day only from the day just before it.
+        // After days without readings it would be counted for time it wasn't measured.
+        let start_ms = end_ms.saturating_sub(DAY_MS);
+        if self.open.is_none()
+            && self
+                .last
+                .is_some_and(|(_, last_ms)| last_ms < start_ms.saturating_sub(DAY_MS))
+        {
+            self.last = None;
+        }
+
+        let temperature = reading.temperature;
+        let day = self.open.get_or_insert(Day {
+            end_ms,
+            heating: 0.0,
+            cooling: 0.0,
+            min_temperature: temperature,
+            max_temperature: temperature,
+        });
+        day.min_temperature = day.min_temperature.min(temperature);
+        day.max_temperature = day.max_temperature.max(temperature);
+        self.integrate_until(timestamp_ms);
+        self.last = Some((temperature, timestamp_ms));
+        closed
+    }
+
+    /// Close the open day if it ended before `now_ms`, and return its degree days.
+    ///
+    /// Call this periodically so that a day is reported on time even when readings stop.
+    pub fn close_due(&mut self, now_ms: u64) -> Option<DailyDegreeDays> {
+        match self.open {
+            Some(day) if now_ms >= day.end_ms => self.close(day.end_ms),
+            _ => None,
+        }
+    }
+
+    /// The degree days of the open day so far, if there is one.
+    pub fn today(&self) -> Option<DailyDegreeDays> {
+        self.open.map(|day| self.summarize(day))
+    }
+
+    /// The degree days of every closed day since the accumulator was created or reset.
+    pub fn totals(&self) -> DegreeDayTotals {
+        self.totals
+    }
+
+    /// Start the running totals over, for example at the start of a heating season. The open day
+    /// carries on.
+    pub fn reset_totals(&mut self) {
+        self.totals = DegreeDayTotals::default();
+    }
+
+    /// The end of the day that `timestamp_ms` falls in.
+    fn day_end_ms(&self, timestamp_ms: u64) -> u64 {
+        let phase = (timestamp_ms + DAY_MS - self.settings.day_start_ms) % DAY_MS;
+        timestamp_ms + (DAY_MS - phase)
+    }
+
+    /// Integrate the previous temperature from its reading up to `until_ms`, within the open day.
+    fn integrate_until(&mut self, until_ms: u64) {
+        let (Some(day), Some((temperature, last_ms))) = (self.open.as_mut(), self.last) else {
+            return;
+        };
+        let from_ms = last_ms.max(day.end_ms.saturating_sub(DAY_MS));
+        let to_ms = until_ms.min(day.end_ms);
+        if to_ms <= from_ms {
+            return;
+        }
+
+        let days = (to_ms - from_ms) as f32 / DAY_MS as f32;
+        let difference = temperature - self.settings.base_temperature;
+        day.heating += (-difference).max(0.0) * days;
+        day.cooling += difference.max(0.0) * days;
+    }
+
+    /// Close the open day, ending at `end_ms`, and add it to the totals.
+    fn close(&mut self, end_ms: u64) -> Option<DailyDegreeDays> {
+        self.integrate_until(end_ms);
+        let day = self.open.take()?;
+        let summary = self.summarize(day);
+        self.totals.heating += summary.heating;
+        self.totals.cooling += summary.cooling;
+        self.totals.days = self.totals.days.saturating_add(1);
+        Some(summary)
+    }
+
+    /// The degree days of `day`, by the configured method.
+    fn summarize(&self, day: Day) -> DailyDegreeDays {
+        let (heating, cooling) = match self.settings.method {
+            DegreeDayMethod::Integration => (day.heating, day.cooling),
+            DegreeDayMethod::MinMax => {
+                let mean = (day.min_temperature + day.max_temperature) / 2.0;
+                let difference = mean - self.settings.base_temperature;
+                ((-difference).max(0.0), difference.max(0.0))
+            }
+        };
+        DailyDegreeDays {
+            end_ms: day.end_ms,
+            heating,
+            cooling,
+            min_temperature: day.min_temperature,
+            max_temperature: day.max_temperature,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DegreeDayMethod, DegreeDaySettings, DegreeDays, InvalidDegreeDaySettings, DAY_MS};
+    use crate::test_support::at_temperature;
+
+    const HOUR_MS: u64 = 3_600_000;
+
+    fn degree_days(method: DegreeDayMethod) -> DegreeDays {
+        DegreeDays::new(DegreeDaySettings {
+            base_temperature: 18.0,
+            method,
+            day_start_ms: 0,
+        })
+        .unwrap()
+    }
+
+    fn close(a: f32, b: f32) -> bool {
+        (a - b).abs() < 1e-4
+    }
+
+    /// Integration adds up the time-weighted difference, split at the day boundary.
+    #[test]
+    fn integration() {
+        let mut degree_days = degree_days(DegreeDayMethod::Integration);
+        // 12 hours at 10C, 6 hours at 22C, then 10C across midnight into the next day.
+        assert_eq!(degree_days.update(at_temperature(10.0), 0), None);
+        assert_eq!(degree_days.update(at_temperature(22.0), 12 * HOUR_MS), None);
+        assert_eq!(degree_days.update(at_temperature(10.0), 18 * HOUR_MS), None);
+
+        let day = degree_days
+            .update(at_temperature(30.0), 30 * HOUR_MS)
+            .unwrap();
+        assert_eq!(day.end_ms, DAY_MS);
+        // 8C for 18 hours below, and 4C for 6 hours above.
+        assert!(close(day.heating, 8.0 * 18.0 / 24.0), "{}", day.heating);
+        assert!(close(day.cooling, 4.0 * 6.0 / 24.0), "{}", day.cooling);
+        assert_eq!((day.min_temperature, day.max_temperature), (10.0, 22.0));
+
+        // 10C carried on for the first 6 hours of the second day.
+        let today = degree_days.today().unwrap();
+        assert!(close(today.heating, 8.0 * 6.0 / 24.0), "{}", today.heating);
+        assert_eq!(today.cooling, 0.0);
+
+        let second = degree_days.close_due(2 * DAY_MS).unwrap();
+        assert!(
+            close(second.cooling, 12.0 * 18.0 / 24.0),
+            "{}",
+            second.cooling
+        );
+        let totals = degree_days.totals();
+        assert_eq!(totals.days, 2);
+        assert!(close(totals.heating, 6.0 + 2.0));
+        assert!(close(totals.cooling, 1.0 + 9.0));
+
+        degree_days.reset_totals();
+        assert_eq!(degree_days.totals().days, 0);
+    }
+
+    /// The last temperature isn't carried over days without readings.
+    #[test]
+    fn integration_skips_days_without_readings() {
+        let mut degree_days = degree_days(DegreeDayMethod::Integration);
+        degree_days.update(at_temperature(10.0), 23 * HOUR_MS);
+
+        // The next reading is at noon three days later.
+        let day = degree_days
+            .update(at_temperature(20.0), 3 * DAY_MS + 12 * HOUR_MS)
+            .unwrap();
+        assert_eq!(day.end_ms, DAY_MS);
+        assert!(close(day.heating, 8.0 / 24.0), "{}", day.heating);
+        assert_eq!(degree_days.today().unwrap().heating, 0.0);
+
+        let last = degree_days.close_due(4 * DAY_MS).unwrap();
+        assert_eq!(last.heating, 0.0);
+        assert!(close(last.cooling, 2.0 * 12.0 / 24.0), "{}", last.cooling);
+        assert_eq!(degree_days.totals().days, 2);
+    }
+
+    /// The min/max method uses the mean of the day's extremes, and days start at the offset.
+    #[test]
+    fn min_max_with_day_start() {
+        let mut degree_days = DegreeDays::new(DegreeDaySettings {
+            base_temperature: 10.0,
+            method: DegreeDayMethod::MinMax,
+            day_start_ms: 9 * HOUR_MS,
+        })
+        .unwrap();
+        // The day from 9am to 9am.
+        degree_days.update(at_temperature(8.0), 10 * HOUR_MS);
+        degree_days.update(at_temperature(24.0), 15 * HOUR_MS);
+        degree_days.update(at_temperature(6.0), 32 * HOUR_MS);
+        // Out of order, ignored.
+        assert_eq!(degree_days.update(at_temperature(50.0), 20 * HOUR_MS), None);
+
+        let day = degree_days
+            .update(at_temperature(12.0), 33 * HOUR_MS)
+            .unwrap();
+        assert_eq!(day.end_ms, 33 * HOUR_MS);
+        // Growing degree days, (6 + 24) / 2 - 10.
+        assert_eq!(day.cooling, 5.0);
+        assert_eq!(day.heating, 0.0);
+        assert_eq!(degree_days.today().unwrap().end_ms, 57 * HOUR_MS);
+
+        // Before the first day start on the clock.
+        let mut early = DegreeDays::new(degree_days.settings).unwrap();
+        early.update(at_temperature(12.0), HOUR_MS);
+        assert_eq!(early.today().unwrap().end_ms, 9 * HOUR_MS);
+    }
+
+    /// Settings that can't work are rejected.
+    #[test]
+    fn rejects_invalid_settings() {
+        let settings = DegreeDaySettings {
+            base_temperature: 18.0,
+            method: DegreeDayMethod::Integration,
+            day_start_ms: DAY_MS,
+        };
+        assert_eq!(DegreeDays::new(settings), Err(InvalidDegreeDaySettings));
+        let settings = DegreeDaySettings {
+            base_temperature: f32::INFINITY,
+            day_start_ms: 0,
+            ..settings
+        };
+        assert_eq!(DegreeDays::new(settings), Err(InvalidDegreeDaySettings));
+    }
+}
@@ -0,0 +1,389 @@
+//! Time spent outside limits, for conservation and storage.
+//!
+//! Collections care is often judged by how long the air spent outside its limits, for example the
+//! hours above 60% or below 35% relative humidity. `ExposureAccumulator` adds up the time
+//! timestamped readings spend past each of a set of `ExposureBand`s, and the longest continuous
+//! excursion past each:
+//!
+//!     use aht20_driver::{ExposureAccumulator, ExposureBand, Limit, Quantity, SensorReading};
+//!
+//!     let mut exposure = ExposureAccumulator::new([
+//!         ExposureBand { quantity: Quantity::Humidity, limit: Limit::High(60.0) },
+//!         ExposureBand { quantity: Quantity::Humidity, limit: Limit::Low(35.0) },
+//!     ])
+//!     .unwrap();
+//!     let hour_ms = 3_600_000;
+//!     exposure.update(SensorReading { humidity: 65.0, temperature: 20.0 }, 0);
+//!     exposure.update(SensorReading { humidity: 50.0, temperature: 20.0 }, 2 * hour_ms);
+//!
+//!     assert_eq!(exposure.exposure(0).unwrap().total_ms, 2 * hour_ms);
+//!     assert_eq!(exposure.exposure(1).unwrap().total_ms, 0);
+//!
+//! Each reading's conditions are taken to hold until the next one.
+//!
+//! The totals can be saved with `save`, to flash or EEPROM for example, and restored with
+//! `restore` after a restart. The time between the last reading before saving and the first
+//! reading after restoring isn't counted, since timestamps usually start over on a restart. The
+//! bands are saved along with the totals, and totals saved for different bands aren't restored.
+
+use crate::{compute_crc, Limit, Quantity, SensorReading};
+
+/// Marks the first byte of a saved state.
+const MAGIC: u8 = 0xE5;
+
+/// The layout version of a saved state, bumped if the meaning of the bytes changes.
+const VERSION: u8 = 1;
+
+/// The bytes before the bands in a saved state: a marker, the layout version and the number of
+/// bands.
+const HEADER_LEN: usize = 3;
+
+/// The bytes defining a band in a saved state: the quantity, the kind of limit and its value.
+const DEFINITION_LEN: usize = 6;
+
+/// The bytes for each band in a saved state: its definition, then the three times.
+const BAND_LEN: usize = DEFINITION_LEN + 24;
+
+/// A limit to track the time past.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "use-defmt", derive(defmt::Format))]
+pub struct ExposureBand {
+    /// What the limit applies to.
+    pub quantity: Quantity,
+    /// The limit, in the quantity's units.
+    pub limit: Limit,
+}
+
+impl ExposureBand {
+    /// Whether `reading` is past the limit.
+    fn exceeded_by(&self, reading: SensorReading) -> bool {
+        let value = reading.get(self.quantity);
+        match self.limit {
+            Limit::High(limit) => value > limit,
+            Limit::Low(limit) => value < limit,
+        }
+    }
+
+    /// The band as saved by `ExposureAccumulator::save`.
+    fn definition(&self) -> [u8; DEFINITION_LEN] {
+        let quantity = match self.quantity {
+            Quantity::Temperature => 0,
+            Quantity::Humidity => 1,
+            Quantity::DewPoint => 2,
+        };
+        let (kind, limit) = match self.limit {
+            Limit::High(limit) => (0, limit),
+            Limit::Low(limit) => (1, limit),
+        };
+        let mut definition = [quantity, kind, 0, 0, 0, 0];
+        definition[2..].copy_from_slice(&limit.to_le_bytes());
+        definition
+    }
+}
+
+/// The time spent past one band's limit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "use-defmt", derive(defmt::Format))]
+pub struct Exposure {
+    /// The total time past the limit, in milliseconds.
+    pub total_ms: u64,
+    /// The longest continuous time past the limit, in milliseconds.
+    pub longest_ms: u64,
+    /// How long the current excursion past the limit has lasted, in milliseconds. 0 when the
+    /// latest reading is within the limit.
+    pub current_ms: u64,
+}
+
+/// The error from `ExposureAccumulator::new`, for a band whose limit isn't a finite number.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "use-defmt", derive(defmt::Format))]
+pub struct InvalidExposureBand;
+
+impl core::fmt::Display for InvalidExposureBand {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> Result<(), core::fmt::Error> {
+        write!(f, "invalid exposure band")
+    }
+}
+
+impl core::error::Error for InvalidExposureBand {}
+
+/// Adds up the time readings spend past `N` limits.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ExposureAccumulator<const N: usize> {
+    bands: [ExposureBand; N],
+    exposures: [Exposure; N],
+    /// When the previous reading was taken, and which bands it was past.
+    last: Option<(u64, [bool; N])>,
+}
+
+impl<const N: usize> ExposureAccumulator<N> {
+    /// The number of bytes `save` needs.
+    pub const SAVED_LEN: usize = HEADER_LEN + N * BAND_LEN + 1;
+
+    /// Create an accumulator with no time counted. Returns `InvalidExposureBand` if a band's limit
+    /// isn't finite.
+    pub fn new(bands: [ExposureBand; N]) -> Result<Self, InvalidExposureBand> {
+        const { assert!(N <= u8::MAX as usize, "too many exposure bands") };
+        let finite = bands.iter().all(|band| match band.limit {
+            Limit::High(limit) | Limit::Low(limit) => limit.is_finite(),
+        });
+        if !finite {
+            return Err(InvalidExposureBand);
+        }
+        Ok(ExposureAccumulator {
+            bands,
+            exposures: [Exposure::default(); N],
+            last: None,
+        })
+    }
+
+    /// Add a reading taken at `timestamp_ms`.
+    ///
+    /// The time since the previous reading is counted for each band the previous reading was
+    /// past. A reading older than the previous one is ignored.
+    pub fn update(&mut self, reading: SensorReading, timestamp_ms: u64) {
+        let past = self.bands.map(|band| band.exceeded_by(reading));
+
+        if let Some((last_ms, last_past)) = self.last {
+            if timestamp_ms < last_ms {
+                return;
+            }
+            let elapsed_ms = timestamp_ms - last_ms;
+            for (exposure, was_past) in self.exposures.iter_mut().zip(last_past) {
+                if was_past {
+                    exposure.total_ms = exposure.total_ms.saturating_add(elapsed_ms);
+                    exposure.current_ms = exposure.current_ms.saturating_add(elapsed_ms);
+                    exposure.longest_ms = exposure.longest_ms.max(exposure.current_ms);
+                }
+            }
+        }
+
+        for (exposure, is_past) in self.exposures.iter_mut().zip(past) {
+            if !is_past {
+                exposure.current_ms = 0;
+            }
+        }
+        self.last = Some((timestamp_ms, past));
+    }
+
+    /// The time spent past the band at index `band`, or `None` for an index out of range.
+    pub fn exposure(&self, band: usize) -> Option<&Exposure> {
+        self.exposures.get(band)
+    }
+
+    /// The time spent past every band, in the order given to `new`.
+    pub fn exposures(&self) -> &[Exposure; N] {
+        &self.exposures
+    }
+
+    /// The bands, in the order given to `new`.
+    pub fn bands(&self) -> &[ExposureBand; N] {
+        &self.bands
+    }
+
+    /// Start counting from zero, for example at the start of a reporting period.
+    pub fn reset(&mut self) {
+        self.exposures = [Exposure::default(); N];
+        self.last = None;
+    }
+
+    /// Write the totals to `buffer`, and return the number of bytes written, `SAVED_LEN`. Returns
+    /// `None` if the buffer is shorter than that.
+    pub fn save(&self, buffer: &mut [u8]) -> Option<usize> {
+        let saved = buffer.get_mut(..Self::SAVED_LEN)?;
+        saved[..HEADER_LEN].copy_from_slice(&[MAGIC, VERSION, N as u8]);
+        for ((chunk, band), exposure) in saved[HEADER_LEN..]
+            .chunks_exact_mut(BAND_LEN)
+            .zip(&self.bands)
+            .zip(&self.exposures)
+        {
+            let (definition, times) = chunk.split_at_mut(DEFINITION_LEN);
+            definition.copy_from_slice(&band.definition());
+            times[..8].copy_from_slice(&exposure.total_ms.to_le_bytes());
+            times[8..16].copy_from_slice(&exposure.longest_ms.to_le_bytes());
+            times[16..].copy_from_slice(&exposure.current_ms.to_le_bytes());
+        }
+        let (data, crc) = saved.split_at_mut(Self::SAVED_LEN - 1);
+        crc[0] = compute_crc(data);
+        Some(Self::SAVED_LEN)
+    }
+
+    /// Restore the totals written by `save`.
+    ///
+    /// An excursion that was going on when the totals were saved carries on if the first reading
+    /// after restoring is still past the limit. Returns `false`, and leaves the accumulator as it
+    /// was, if `bytes` aren't a valid saved state for this accumulator's bands.
+    pub fn restore(&mut self, bytes: &[u8]) -> bool {
+        let Some(saved) = bytes.get(..Self::SAVED_LEN) else {
+            return false;
+        };
+        let (data, crc) = saved.split_at(Self::SAVED_LEN - 1);
+        let same_bands = data[HEADER_LEN..]
+            .chunks_exact(BAND_LEN)
+            .zip(&self.bands)
+            .all(|(chunk, band)| chunk[..DEFINITION_LEN] == band.definition());
+        if data[..HEADER_LEN] != [MAGIC, VERSION, N as u8]
+            || compute_crc(data) != crc[0]
+            || !same_bands
+        {
+            #[cfg(feature = "use-defmt")]
+            defmt::debug!("ExposureAccumulator: saved state not valid.");
+            return false;
+        }
+
+        let read = |chunk: &[u8]| {
+            let mut bytes = [0; 8];
+            bytes.copy_from_slice(chunk);
+            u64::from_le_bytes(bytes)
+        };
+        for (chunk, exposure) in data[HEADER_LEN..]
+            .chunks_exact(BAND_LEN)
+            .zip(self.exposures.iter_mut())
+        {
+            let times = &chunk[DEFINITION_LEN..];
+            *exposure = Exposure {
+                total_ms: read(&times[..8]),
+                longest_ms: read(&times[8..16]),
+                current_ms: read(&times[16..]),
+            };
+        }
+        self.last = None;
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Exposure, ExposureAccumulator, ExposureBand, InvalidExposureBand};
+    use crate::test_support::at_humidity;
+    use crate::{Limit, Quantity};
+
+    const HOUR_MS: u64 = 3_600_000;
+
+    fn accumulator() -> ExposureAccumulator<2> {
+        ExposureAccumulator::new([
+            ExposureBand {
+                quantity: Quantity::Humidity,
+                limit: Limit::High(60.0),
+            },
+            ExposureBand {
+                quantity: Quantity::Humidity,
+                limit: Limit::Low(35.0),
+            },
+        ])
+        .unwrap()
+    }
+
+    /// Time is added up per band, with the longest excursion.
+    #[test]
+    fn accumulates_time_past_limits() {
+        let mut exposure = accumulator();
+        // 3 hours above, 1 within, 2 below, 1 above, then within.
+        for (hour, humidity) in [(0, 65.0), (3, 50.0), (4, 30.0), (6, 62.0), (7, 50.0)] {
+            exposure.update(at_humidity(humidity), hour * HOUR_MS);
+        }
+        // Out of order, ignored.
+        exposure.update(at_humidity(99.0), 5 * HOUR_MS);
+
+        assert_eq!(
+            exposure.exposures(),
+            &[
+                Exposure {
+                    total_ms: 4 * HOUR_MS,
+                    longest_ms: 3 * HOUR_MS,
+                    current_ms: 0,
+                },
+                Exposure {
+                    total_ms: 2 * HOUR_MS,
+                    longest_ms: 2 * HOUR_MS,
+                    current_ms: 0,
+                },
+            ]
+        );
+
+        exposure.update(at_humidity(70.0), 8 * HOUR_MS);
+        exposure.update(at_humidity(70.0), 9 * HOUR_MS);
+        assert_eq!(exposure.exposure(0).unwrap().current_ms, HOUR_MS);
+        assert_eq!(exposure.exposure(2), None);
+
+        exposure.reset();
+        assert_eq!(exposure.exposures(), &[Exposure::default(); 2]);
+    }
+
+    /// Saved totals are restored, and an excursion in progress carries on.
+    #[test]
+    fn save_and_restore() {
+        let mut exposure = accumulator();
+        exposure.update(at_humidity(65.0), 0);
+        exposure.update(at_humidity(65.0), 2 * HOUR_MS);
+
+        let mut buffer = [0; 64];
+        assert_eq!(exposure.save(&mut buffer[..10]), None);
+        let len = exposure.save(&mut buffer).unwrap();
+        assert_eq!(len, ExposureAccumulator::<2>::SAVED_LEN);
+
+        // After a restart, with the clock starting over.
+        let mut restored = accumulator();
+        assert!(restored.restore(&buffer[..len]));
+        restored.update(at_humidity(65.0), 0);
+        restored.update(at_humidity(50.0), HOUR_MS);
+        let high = restored.exposure(0).unwrap();
+        assert_eq!(high.total_ms, 3 * HOUR_MS);
+        assert_eq!(high.longest_ms, 3 * HOUR_MS);
+
+        // Corrupted or mismatched states are rejected.
+        buffer[5] ^= 1;
+        assert!(!restored.restore(&buffer[..len]));
+        assert_eq!(restored.exposure(0).unwrap().total_ms, 3 * HOUR_MS);
+        let single = ExposureAccumulator::new([ExposureBand {
+            quantity: Quantity::Humidity,
+            limit: Limit::High(60.0),
+        }])
+        .unwrap();
+        let mut single_buffer = [0; 64];
+        let single_len = single.save(&mut single_buffer).unwrap();
+        assert!(!restored.restore(&single_buffer[..single_len]));
+    }
+
+    /// Totals saved for different bands aren't restored.
+    #[test]
+    fn restore_rejects_other_bands() {
+        let mut exposure = accumulator();
+        exposure.update(at_humidity(65.0), 0);
+        exposure.update(at_humidity(65.0), HOUR_MS);
+        let mut buffer = [0; 64];
+        let len = exposure.save(&mut buffer).unwrap();
+
+        for limit in [Limit::High(65.0), Limit::Low(60.0)] {
+            let mut other = ExposureAccumulator::new([
+                ExposureBand {
+                    quantity: Quantity::Humidity,
+                    limit,
+                },
+                accumulator().bands()[1],
+            ])
+            .unwrap();
+            assert!(!other.restore(&buffer[..len]));
+            assert_eq!(other.exposures(), &[Exposure::default(); 2]);
+        }
+        let mut other = ExposureAccumulator::new([
+            ExposureBand {
+                quantity: Quantity::DewPoint,
+                limit: Limit::High(60.0),
+            },
+            accumulator().bands()[1],
+        ])
+        .unwrap();
+        assert!(!other.restore(&buffer[..len]));
+    }
+
+    /// Limits that aren't finite are rejected.
+    #[test]
+    fn rejects_invalid_bands() {
+        let band = ExposureBand {
+            quantity: Quantity::Temperature,
+            limit: Limit::Low(f32::NAN),
+        };
+        assert_eq!(ExposureAccumulator::new([band]), Err(InvalidExposureBand));
+    }
+}
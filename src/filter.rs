@@ -0,0 +1,439 @@
+//! Streaming filters to smooth readings.
+//!
+//! Each filter takes readings one at a time with `Filter::update`, and returns the smoothed
+//! reading. Temperature and humidity are filtered separately. A filter can be used on its own, or
+//! attached to an initialized driver with `with_filter`, so that `measure` returns filtered
+//! readings:
+//!
+//!     # use embedded_hal_mock::eh1::delay::NoopDelay as MockDelay;
+//!     # use embedded_hal_mock::eh1::i2c::Mock as I2cMock;
+//!     # use embedded_hal_mock::eh1::i2c::Transaction;
+//!     use aht20_driver::{Ema, AHT20, SENSOR_ADDRESS};
+//!
+//!     # let expectations = vec![
+//!     #     Transaction::read(SENSOR_ADDRESS, vec![0b0001_1000]),
+//!     #     Transaction::write(SENSOR_ADDRESS, vec![0xAC, 0x33, 0x00]),
+//!     #     Transaction::read(SENSOR_ADDRESS, vec![0b0001_1000]),
+//!     #     Transaction::read(SENSOR_ADDRESS, vec![0x1c, 0x65, 0xb4, 0x25, 0xcd, 0x26, 0xc6]),
+//!     # ];
+//!     # let mock_i2c = I2cMock::new(&expectations);
+//!     # let mut delay = MockDelay::new();
+//!     let aht20 = AHT20::new(mock_i2c, SENSOR_ADDRESS).init(&mut delay).unwrap();
+//!     let mut aht20 = aht20.with_filter(Ema::new(0.2).unwrap());
+//!
+//!     let smoothed = aht20.measure(&mut delay).unwrap();
+//!     # let (aht20, _) = aht20.release();
+//!     # aht20.destroy().done();
+//!
+//! The filters are:
+//!
+//! * `Ema`, an exponential moving average. Cheap, and reacts faster with a larger alpha.
+//! * `MovingAverage`, the average of the last `N` readings.
+//! * `Median`, the median of the last `N` readings, which ignores single spikes entirely.
+//! * `Kalman`, a one-dimensional Kalman filter tuned with the process and measurement noise.
+
+use embedded_hal::delay::DelayNs;
+use embedded_hal::i2c::I2c;
+
+use crate::{Error, Ready, SensorReading, AHT20};
+
+/// A filter that smooths a stream of readings.
+pub trait Filter {
+    /// Add a reading, and return the filtered reading.
+    fn update(&mut self, reading: SensorReading) -> SensorReading;
+
+    /// Forget all readings, for example after a gap in measurements.
+    fn reset(&mut self);
+}
+
+/// The error from `Ema::new` and `Kalman::new`, for a parameter out of range.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "use-defmt", derive(defmt::Format))]
+pub struct InvalidFilter;
+
+impl core::fmt::Display for InvalidFilter {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> Result<(), core::fmt::Error> {
+        write!(f, "invalid filter parameter")
+    }
+}
+
+impl core::error::Error for InvalidFilter {}
+
+/// An exponential moving average.
+///
+/// Each filtered value moves `alpha` of the way towards the new reading. The first reading is
+/// passed through as is.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Ema {
+    alpha: f32,
+    state: Option<SensorReading>,
+}
+
+impl Ema {
+    /// Create the filter. Returns `InvalidFilter` unless `0 < alpha <= 1`.
+    pub fn new(alpha: f32) -> Result<Self, InvalidFilter> {
+        if !(alpha > 0.0 && alpha <= 1.0) {
+            return Err(InvalidFilter);
+        }
+        Ok(Ema { alpha, state: None })
+    }
+}
+
+impl Filter for Ema {
+    fn update(&mut self, reading: SensorReading) -> SensorReading {
+        let filtered = match self.state {
+            Some(state) => SensorReading {
+                humidity: state.humidity + self.alpha * (reading.humidity - state.humidity),
+                temperature: state.temperature
+                    + self.alpha * (reading.temperature - state.temperature),
+            },
+            None => reading,
+        };
+        self.state = Some(filtered);
+        filtered
+    }
+
+    fn reset(&mut self) {
+        self.state = None;
+    }
+}
+
+/// The last `N` readings, oldest overwritten first.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Window<const N: usize> {
+    readings: [SensorReading; N],
+    len: usize,
+    next: usize,
+}
+
+impl<const N: usize> Window<N> {
+    fn new() -> Self {
+        const {
+            assert!(
+                N > 0,
+                "the filter window needs room for at least one reading"
+            )
+        };
+        Window {
+            readings: [SensorReading {
+                humidity: 0.0,
+                temperature: 0.0,
+            }; N],
+            len: 0,
+            next: 0,
+        }
+    }
+
+    fn push(&mut self, reading: SensorReading) {
+        self.readings[self.next] = reading;
+        self.next = (self.next + 1) % N;
+        self.len = (self.len + 1).min(N);
+    }
+
+    /// The readings held, in no particular order.
+    fn readings(&self) -> &[SensorReading] {
+        &self.readings[..self.len]
+    }
+}
+
+/// The average of the last `N` readings.
+///
+/// Until `N` readings have been seen, this is the average of the readings so far.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MovingAverage<const N: usize> {
+    window: Window<N>,
+}
+
+impl<const N: usize> MovingAverage<N> {
+    /// Create the filter.
+    pub fn new() -> Self {
+        MovingAverage {
+            window: Window::new(),
+        }
+    }
+}
+
+impl<const N: usize> Default for MovingAverage<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> Filter for MovingAverage<N> {
+    fn update(&mut self, reading: SensorReading) -> SensorReading {
+        self.window.push(reading);
+        let readings = self.window.readings();
+        let count = readings.len() as f32;
+        SensorReading {
+            humidity: readings.iter().map(|r| r.humidity).sum::<f32>() / count,
+            temperature: readings.iter().map(|r| r.temperature).sum::<f32>() / count,
+        }
+    }
+
+    fn reset(&mut self) {
+        self.window = Window::new();
+    }
+}
+
+/// The median of the last `N` readings.
+///
+/// Temperature and humidity medians are taken separately, so they may come from different
+/// readings. With an even number of readings, the two middle values are averaged.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Median<const N: usize> {
+    window: Window<N>,
+}
+
+impl<const N: usize> Median<N> {
+    /// Create the filter.
+    pub fn new() -> Self {
+        Median {
+            window: Window::new(),
+        }
+    }
+}
+
+impl<const N: usize> Default for Median<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> Filter for Median<N> {
+    fn update(&mut self, reading: SensorReading) -> SensorReading {
+        self.window.push(reading);
+        let readings = self.window.readings();
+        let mut humidity = [0.0; N];
+        let mut temperature = [0.0; N];
+        for (i, reading) in readings.iter().enumerate() {
+            humidity[i] = reading.humidity;
+            temperature[i] = reading.temperature;
+        }
+        SensorReading {
+            humidity: median(&mut humidity[..readings.len()]),
+            temperature: median(&mut temperature[..readings.len()]),
+        }
+    }
+
+    fn reset(&mut self) {
+        self.window = Window::new();
+    }
+}
+
+/// The median of the non-empty `values`, which are sorted in place.
+fn median(values: &mut [f32]) -> f32 {
+    values.sort_unstable_by(f32::total_cmp);
+    let len = values.len();
+    (values[(len - 1) / 2] + values[len / 2]) / 2.0
+}
+
+/// The estimate for one quantity in `Kalman`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Estimate {
+    value: f32,
+    variance: f32,
+}
+
+/// A one-dimensional Kalman filter, treating the true value as a random walk.
+///
+/// `process_noise` is how much the true value is expected to change between readings, and
+/// `measurement_noise` how much a reading scatters around the true value, both as variances. A
+/// higher measurement noise relative to the process noise gives smoother, slower output. The
+/// same settings are used for temperature and humidity.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Kalman {
+    process_noise: f32,
+    measurement_noise: f32,
+    state: Option<(Estimate, Estimate)>,
+}
+
+impl Kalman {
+    /// Create the filter. Returns `InvalidFilter` unless both noise values are finite, the process
+    /// noise isn't negative and the measurement noise is positive.
+    pub fn new(process_noise: f32, measurement_noise: f32) -> Result<Self, InvalidFilter> {
+        if !process_noise.is_finite()
+            || !measurement_noise.is_finite()
+            || process_noise < 0.0
+            || measurement_noise <= 0.0
+        {
+            return Err(InvalidFilter);
+        }
+        Ok(Kalman {
+            process_noise,
+            measurement_noise,
+            state: None,
+        })
+    }
+
+    /// Fold `measurement` into `estimate`.
+    fn step(&self, estimate: Estimate, measurement: f32) -> Estimate {
+        let predicted_variance = estimate.variance + self.process_noise;
+        let gain = predicted_variance / (predicted_variance + self.measurement_noise);
+        Estimate {
+            value: estimate.value + gain * (measurement - estimate.value),
+            variance: (1.0 - gain) * predicted_variance,
+        }
+    }
+}
+
+impl Filter for Kalman {
+    fn update(&mut self, reading: SensorReading) -> SensorReading {
+        let (humidity, temperature) = match self.state {
+            Some((humidity, temperature)) => (
+                self.step(humidity, reading.humidity),
+                self.step(temperature, reading.temperature),
+            ),
+            // Start from the first reading, as uncertain as a single measurement.
+            None => (
+                Estimate {
+                    value: reading.humidity,
+                    variance: self.measurement_noise,
+                },
+                Estimate {
+                    value: reading.temperature,
+                    variance: self.measurement_noise,
+                },
+            ),
+        };
+        self.state = Some((humidity, temperature));
+        SensorReading {
+            humidity: humidity.value,
+            temperature: temperature.value,
+        }
+    }
+
+    fn reset(&mut self) {
+        self.state = None;
+    }
+}
+
+/// An initialized AHT20 whose readings go through a `Filter`.
+///
+/// This is returned by `AHT20::with_filter`.
+pub struct AHT20Filtered<I, F>
+where
+    I: I2c,
+    F: Filter,
+{
+    aht20: AHT20<I, Ready>,
+    filter: F,
+}
+
+impl<I> AHT20<I, Ready>
+where
+    I: I2c,
+{
+    /// Pass every reading from `measure` through `filter`.
+    pub fn with_filter<F>(self, filter: F) -> AHT20Filtered<I, F>
+    where
+        F: Filter,
+    {
+        AHT20Filtered {
+            aht20: self,
+            filter,
+        }
+    }
+}
+
+impl<I, F> AHT20Filtered<I, F>
+where
+    I: I2c,
+    F: Filter,
+{
+    /// Measure temperature and humidity, and return the filtered reading.
+    ///
+    /// A failed measurement leaves the filter untouched.
+    pub fn measure(&mut self, delay: &mut impl DelayNs) -> Result<SensorReading, Error<I::Error>> {
+        let reading = self.aht20.measure(delay)?;
+        Ok(self.filter.update(reading))
+    }
+
+    /// Borrow the driver and filter, for example to `reset` the filter.
+    pub fn parts_mut(&mut self) -> (&mut AHT20<I, Ready>, &mut F) {
+        (&mut self.aht20, &mut self.filter)
+    }
+
+    /// Give back the driver and the filter.
+    pub fn release(self) -> (AHT20<I, Ready>, F) {
+        (self.aht20, self.filter)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Ema, Filter, InvalidFilter, Kalman, Median, MovingAverage};
+    use crate::test_support::{at_temperature, measurement, measurement_frame};
+    use crate::{Ready, AHT20, SENSOR_ADDRESS};
+    use embedded_hal_mock::eh1::delay::NoopDelay as MockDelay;
+    use embedded_hal_mock::eh1::i2c::Mock as I2cMock;
+
+    /// The EMA moves alpha of the way to each new reading.
+    #[test]
+    fn ema() {
+        let mut ema = Ema::new(0.5).unwrap();
+        assert_eq!(ema.update(at_temperature(20.0)).temperature, 20.0);
+        assert_eq!(ema.update(at_temperature(22.0)).temperature, 21.0);
+        assert_eq!(ema.update(at_temperature(22.0)).temperature, 21.5);
+        ema.reset();
+        assert_eq!(ema.update(at_temperature(10.0)).temperature, 10.0);
+
+        assert_eq!(Ema::new(0.0), Err(InvalidFilter));
+        assert_eq!(Ema::new(f32::NAN), Err(InvalidFilter));
+    }
+
+    /// The moving average covers the last N readings.
+    #[test]
+    fn moving_average() {
+        let mut average = MovingAverage::<3>::new();
+        assert_eq!(average.update(at_temperature(10.0)).temperature, 10.0);
+        assert_eq!(average.update(at_temperature(20.0)).temperature, 15.0);
+        assert_eq!(average.update(at_temperature(30.0)).temperature, 20.0);
+        assert_eq!(average.update(at_temperature(40.0)).temperature, 30.0);
+        assert_eq!(average.update(at_temperature(40.0)).humidity, 50.0);
+    }
+
+    /// The median ignores a single spike.
+    #[test]
+    fn median() {
+        let mut median = Median::<3>::new();
+        assert_eq!(median.update(at_temperature(20.0)).temperature, 20.0);
+        assert_eq!(median.update(at_temperature(22.0)).temperature, 21.0);
+        assert_eq!(median.update(at_temperature(85.0)).temperature, 22.0);
+        assert_eq!(median.update(at_temperature(21.0)).temperature, 22.0);
+    }
+
+    /// The Kalman filter settles on a steady value, and damps noise around it.
+    #[test]
+    fn kalman() {
+        let mut kalman = Kalman::new(0.001, 0.1).unwrap();
+        assert_eq!(kalman.update(at_temperature(20.0)).temperature, 20.0);
+        let mut filtered = 0.0;
+        for i in 0..50 {
+            let noise = if i % 2 == 0 { 0.5 } else { -0.5 };
+            filtered = kalman.update(at_temperature(25.0 + noise)).temperature;
+        }
+        assert!((filtered - 25.0).abs() < 0.5);
+        assert!(filtered > 23.0);
+
+        assert_eq!(Kalman::new(0.1, 0.0), Err(InvalidFilter));
+    }
+
+    /// Measurements from a driver with a filter are filtered.
+    #[test]
+    fn measure_filtered() {
+        let measurement = measurement(measurement_frame());
+        let mock_i2c = I2cMock::new(&measurement);
+        let mut mock_delay = MockDelay::new();
+
+        let aht20: AHT20<_, Ready> = AHT20::new(mock_i2c, SENSOR_ADDRESS).into_state();
+        let mut ema = Ema::new(0.5).unwrap();
+        ema.update(at_temperature(20.0));
+        let mut aht20 = aht20.with_filter(ema);
+        let filtered = aht20.measure(&mut mock_delay).unwrap();
+        // Half way between 20C and the 22.52C measured.
+        assert!(filtered.temperature > 21.2 && filtered.temperature < 21.3);
+
+        let (aht20, _) = aht20.release();
+        aht20.destroy().done();
+    }
+}
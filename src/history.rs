@@ -0,0 +1,252 @@
+//! A rolling history of readings, with running statistics.
+//!
+//! `History` keeps the last `N` timestamped readings in a fixed-size buffer, for showing recent
+//! history. Alongside it, it keeps the minimum, maximum, mean and standard deviation of every
+//! reading since the statistics were last reset. These are updated as each reading comes in using
+//! Welford's method, so they cover more than the buffer holds and never iterate over it. Resetting
+//! the statistics at midnight gives "today's min/max":
+//!
+//!     use aht20_driver::{History, SensorReading};
+//!
+//!     let mut history: History<64> = History::new();
+//!     history.push(SensorReading { humidity: 40.0, temperature: 21.0 }, 0);
+//!     history.push(SensorReading { humidity: 44.0, temperature: 23.0 }, 60_000);
+//!
+//!     let temperature = history.statistics().temperature;
+//!     assert_eq!(temperature.min(), Some(21.0));
+//!     assert_eq!(temperature.max(), Some(23.0));
+//!     assert_eq!(temperature.mean(), Some(22.0));
+//!
+//!     // At midnight.
+//!     history.reset_statistics();
+
+use heapless::HistoryBuffer;
+
+use crate::SensorReading;
+
+/// A reading, and when it was taken.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TimestampedReading {
+    /// The reading.
+    pub reading: SensorReading,
+    /// When the reading was taken, in milliseconds from a monotonic clock.
+    pub timestamp_ms: u64,
+}
+
+/// Running statistics for one quantity, updated one value at a time.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Statistics {
+    count: u32,
+    mean: f32,
+    /// The sum of squared differences from the mean, from Welford's method.
+    m2: f32,
+    min: f32,
+    max: f32,
+}
+
+impl Statistics {
+    /// Statistics with no values yet.
+    pub fn new() -> Self {
+        Statistics::default()
+    }
+
+    /// Add a value.
+    pub fn add(&mut self, value: f32) {
+        if self.count == 0 {
+            self.min = value;
+            self.max = value;
+        } else {
+            self.min = self.min.min(value);
+            self.max = self.max.max(value);
+        }
+
+        self.count = self.count.saturating_add(1);
+        let delta = value - self.mean;
+        self.mean += delta / self.count as f32;
+        self.m2 += delta * (value - self.mean);
+    }
+
+    /// Forget all values.
+    pub fn reset(&mut self) {
+        *self = Statistics::new();
+    }
+
+    /// How many values have been added.
+    pub fn count(&self) -> u32 {
+        self.count
+    }
+
+    /// The smallest value, or `None` if there are no values.
+    pub fn min(&self) -> Option<f32> {
+        (self.count > 0).then_some(self.min)
+    }
+
+    /// The largest value, or `None` if there are no values.
+    pub fn max(&self) -> Option<f32> {
+        (self.count > 0).then_some(self.max)
+    }
+
+    /// The mean, or `None` if there are no values.
+    pub fn mean(&self) -> Option<f32> {
+        (self.count > 0).then_some(self.mean)
+    }
+
+    /// The sample variance, or `None` if there are fewer than two values.
+    pub fn variance(&self) -> Option<f32> {
+        (self.count > 1).then(|| self.m2 / (self.count - 1) as f32)
+    }
+
+    /// The sample standard deviation, or `None` if there are fewer than two values.
+    pub fn std_dev(&self) -> Option<f32> {
+        self.variance().map(libm::sqrtf)
+    }
+}
+
+/// Running statistics for temperature and humidity.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct ReadingStatistics {
+    /// Temperature statistics, in degrees Celsius.
+    pub temperature: Statistics,
+    /// Relative humidity statistics, in percent.
+    pub humidity: Statistics,
+}
+
+impl ReadingStatistics {
+    /// Add a reading to both statistics.
+    pub fn add(&mut self, reading: SensorReading) {
+        self.temperature.add(reading.temperature);
+        self.humidity.add(reading.humidity);
+    }
+
+    /// Forget all readings.
+    pub fn reset(&mut self) {
+        self.temperature.reset();
+        self.humidity.reset();
+    }
+}
+
+/// The last `N` timestamped readings, and statistics since the last reset.
+pub struct History<const N: usize> {
+    readings: HistoryBuffer<TimestampedReading, N>,
+    statistics: ReadingStatistics,
+}
+
+impl<const N: usize> History<N> {
+    /// An empty history.
+    pub fn new() -> Self {
+        History {
+            readings: HistoryBuffer::new(),
+            statistics: ReadingStatistics::default(),
+        }
+    }
+
+    /// Add a reading taken at `timestamp_ms`, replacing the oldest one if the buffer is full.
+    pub fn push(&mut self, reading: SensorReading, timestamp_ms: u64) {
+        self.readings.write(TimestampedReading {
+            reading,
+            timestamp_ms,
+        });
+        self.statistics.add(reading);
+    }
+
+    /// The most recent reading, if there is one.
+    pub fn latest(&self) -> Option<&TimestampedReading> {
+        self.readings.recent()
+    }
+
+    /// The readings in the buffer, oldest first.
+    pub fn iter(&self) -> impl Iterator<Item = &TimestampedReading> {
+        self.readings.oldest_ordered()
+    }
+
+    /// How many readings are in the buffer.
+    pub fn len(&self) -> usize {
+        self.readings.len()
+    }
+
+    /// Whether the buffer is empty.
+    pub fn is_empty(&self) -> bool {
+        self.readings.len() == 0
+    }
+
+    /// The statistics of all readings since the last reset, including ones no longer in the
+    /// buffer.
+    pub fn statistics(&self) -> &ReadingStatistics {
+        &self.statistics
+    }
+
+    /// Start the statistics over, keeping the buffered readings.
+    pub fn reset_statistics(&mut self) {
+        self.statistics.reset();
+    }
+
+    /// Empty the buffer and reset the statistics.
+    pub fn clear(&mut self) {
+        self.readings.clear();
+        self.statistics.reset();
+    }
+}
+
+impl<const N: usize> Default for History<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{History, Statistics};
+    use crate::test_support::at_temperature;
+
+    /// Welford's method gives the same answers as the textbook formulas.
+    #[test]
+    fn statistics() {
+        let mut statistics = Statistics::new();
+        assert_eq!(statistics.mean(), None);
+        assert_eq!(statistics.std_dev(), None);
+
+        for value in [2.0, 4.0, 4.0, 4.0, 5.0, 5.0, 7.0, 9.0] {
+            statistics.add(value);
+        }
+        assert_eq!(statistics.count(), 8);
+        assert_eq!(statistics.min(), Some(2.0));
+        assert_eq!(statistics.max(), Some(9.0));
+        assert_eq!(statistics.mean(), Some(5.0));
+        // The sum of squared differences is 32, over 7.
+        let variance = statistics.variance().unwrap();
+        assert!((variance - 32.0 / 7.0).abs() < 1e-5);
+        assert!((statistics.std_dev().unwrap() - variance.sqrt()).abs() < 1e-5);
+
+        statistics.reset();
+        assert_eq!(statistics.count(), 0);
+        assert_eq!(statistics.min(), None);
+    }
+
+    /// The buffer keeps the last N readings, the statistics cover everything since the reset.
+    #[test]
+    fn history_rolls_over() {
+        let mut history: History<3> = History::new();
+        assert!(history.is_empty());
+        for (i, temperature) in [10.0, 20.0, 30.0, 40.0].into_iter().enumerate() {
+            history.push(at_temperature(temperature), i as u64 * 1000);
+        }
+
+        assert_eq!(history.len(), 3);
+        let timestamps: Vec<u64> = history.iter().map(|r| r.timestamp_ms).collect();
+        assert_eq!(timestamps, [1000, 2000, 3000]);
+        assert_eq!(history.latest().unwrap().reading.temperature, 40.0);
+
+        let temperature = history.statistics().temperature;
+        assert_eq!(temperature.count(), 4);
+        assert_eq!(temperature.min(), Some(10.0));
+        assert_eq!(temperature.mean(), Some(25.0));
+        assert_eq!(history.statistics().humidity.max(), Some(50.0));
+
+        history.reset_statistics();
+        assert_eq!(history.statistics().temperature.count(), 0);
+        assert_eq!(history.len(), 3);
+
+        history.clear();
+        assert!(history.is_empty());
+    }
+}
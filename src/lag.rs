@@ -0,0 +1,242 @@
+//! Compensating for how slowly the sensor responds to change.
+//!
+//! The AHT20 takes around 8 seconds to show 63% of a step change in humidity, and temperature lags
+//! similarly. Treating the sensor as a first-order system, the reading `y` follows the true value
+//! `x` as `dy/dt = (x - y) / τ`, so the true value can be estimated as `x = y + τ·dy/dt`.
+//! `LagCompensator` does this with the rate of change between consecutive timestamped readings.
+//!
+//! Working from a rate of change amplifies jitter, so the rate is smoothed with an exponential
+//! moving average, and the correction is limited to a maximum size.
+//!
+//!     use aht20_driver::{LagCompensation, LagCompensator, SensorReading};
+//!
+//!     let mut compensator = LagCompensator::new(LagCompensation::default()).unwrap();
+//!     # let reading = SensorReading { humidity: 40.0, temperature: 21.0 };
+//!     # let uptime_ms = 0;
+//!     let estimate = compensator.update(reading, uptime_ms);
+//!     # assert_eq!(estimate, reading);
+
+use crate::SensorReading;
+
+/// Settings for `LagCompensator`. The defaults are from the datasheet's response times.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LagCompensation {
+    /// The time constant for temperature, in seconds. 0 turns compensation off.
+    pub temperature_tau_s: f32,
+    /// The time constant for humidity, in seconds. 0 turns compensation off.
+    pub humidity_tau_s: f32,
+    /// The largest correction applied to temperature, in degrees Celsius.
+    pub max_temperature_correction: f32,
+    /// The largest correction applied to humidity, in percent.
+    pub max_humidity_correction: f32,
+    /// How much of each new rate of change is taken into the smoothed rate, between 0 and 1. Lower
+    /// values limit noise more, but react more slowly.
+    pub rate_smoothing: f32,
+}
+
+impl Default for LagCompensation {
+    fn default() -> Self {
+        LagCompensation {
+            temperature_tau_s: 5.0,
+            humidity_tau_s: 8.0,
+            max_temperature_correction: 5.0,
+            max_humidity_correction: 10.0,
+            rate_smoothing: 0.3,
+        }
+    }
+}
+
+impl LagCompensation {
+    /// Check that every setting is finite and in range.
+    fn is_valid(&self) -> bool {
+        let non_negative = |value: f32| value.is_finite() && value >= 0.0;
+        non_negative(self.temperature_tau_s)
+            && non_negative(self.humidity_tau_s)
+            && non_negative(self.max_temperature_correction)
+            && non_negative(self.max_humidity_correction)
+            && self.rate_smoothing > 0.0
+            && self.rate_smoothing <= 1.0
+    }
+}
+
+/// The compensation state for one quantity.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Channel {
+    tau_s: f32,
+    max_correction: f32,
+    /// The last reading, and the smoothed rate of change in units per second.
+    last: Option<(f32, f32)>,
+}
+
+impl Channel {
+    fn new(tau_s: f32, max_correction: f32) -> Self {
+        Channel {
+            tau_s,
+            max_correction,
+            last: None,
+        }
+    }
+
+    /// Estimate the true value from a reading taken `dt_s` seconds after the last one.
+    fn update(&mut self, value: f32, dt_s: f32, rate_smoothing: f32) -> f32 {
+        let rate = match self.last {
+            Some((last_value, rate)) if dt_s > 0.0 => {
+                rate + rate_smoothing * ((value - last_value) / dt_s - rate)
+            }
+            Some((_, rate)) => rate,
+            None => 0.0,
+        };
+        self.last = Some((value, rate));
+
+        let correction = (self.tau_s * rate).clamp(-self.max_correction, self.max_correction);
+        value + correction
+    }
+}
+
+/// The error from `LagCompensator::new`, for a setting that is negative or not finite, or a rate
+/// smoothing that isn't above 0 and at most 1.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "use-defmt", derive(defmt::Format))]
+pub struct InvalidLagCompensation;
+
+impl core::fmt::Display for InvalidLagCompensation {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> Result<(), core::fmt::Error> {
+        write!(f, "invalid lag compensation settings")
+    }
+}
+
+impl core::error::Error for InvalidLagCompensation {}
+
+/// Estimates the true temperature and humidity from lagging readings.
+///
+/// See the module documentation for the model used.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LagCompensator {
+    rate_smoothing: f32,
+    temperature: Channel,
+    humidity: Channel,
+    last_timestamp_ms: Option<u64>,
+}
+
+impl LagCompensator {
+    /// Create the compensator. Returns `InvalidLagCompensation` if a setting is negative or not
+    /// finite, or the rate smoothing isn't above 0 and at most 1.
+    pub fn new(settings: LagCompensation) -> Result<Self, InvalidLagCompensation> {
+        if !settings.is_valid() {
+            return Err(InvalidLagCompensation);
+        }
+        Ok(LagCompensator {
+            rate_smoothing: settings.rate_smoothing,
+            temperature: Channel::new(
+                settings.temperature_tau_s,
+                settings.max_temperature_correction,
+            ),
+            humidity: Channel::new(settings.humidity_tau_s, settings.max_humidity_correction),
+            last_timestamp_ms: None,
+        })
+    }
+
+    /// Add a reading taken at `timestamp_ms`, and return the estimated true values.
+    ///
+    /// The first reading is returned as is. Timestamps should come from a monotonic clock, a
+    /// reading with the same timestamp as the last one reuses the last rate of change. Humidity is
+    /// kept within 0-100%.
+    pub fn update(&mut self, reading: SensorReading, timestamp_ms: u64) -> SensorReading {
+        let dt_s = match self.last_timestamp_ms {
+            Some(last_ms) => timestamp_ms.saturating_sub(last_ms) as f32 / 1000.0,
+            None => 0.0,
+        };
+        self.last_timestamp_ms = Some(timestamp_ms);
+
+        SensorReading {
+            humidity: self
+                .humidity
+                .update(reading.humidity, dt_s, self.rate_smoothing)
+                .clamp(0.0, 100.0),
+            temperature: self
+                .temperature
+                .update(reading.temperature, dt_s, self.rate_smoothing),
+        }
+    }
+
+    /// Forget all readings, for example after a gap in measurements.
+    pub fn reset(&mut self) {
+        self.temperature.last = None;
+        self.humidity.last = None;
+        self.last_timestamp_ms = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{InvalidLagCompensation, LagCompensation, LagCompensator};
+    use crate::test_support::at_humidity;
+
+    /// A sensor following a step from 30% to 60% is compensated back to close to 60%.
+    #[test]
+    fn compensates_step_response() {
+        let mut compensator = LagCompensator::new(LagCompensation {
+            rate_smoothing: 1.0,
+            max_humidity_correction: 50.0,
+            ..LagCompensation::default()
+        })
+        .unwrap();
+
+        for second in 0..20u64 {
+            // The first-order response with the 8s time constant.
+            let sensed = 60.0 - 30.0 * (-(second as f32) / 8.0).exp();
+            let estimate = compensator.update(at_humidity(sensed), second * 1000);
+            if second >= 1 {
+                assert!((estimate.humidity - 60.0).abs() < 2.5, "{estimate:?}");
+                // The raw reading is much further off to begin with.
+                if second < 8 {
+                    assert!((sensed - 60.0).abs() > 10.0);
+                }
+            }
+            // Temperature didn't change, so isn't corrected.
+            assert_eq!(estimate.temperature, 20.0);
+        }
+    }
+
+    /// The correction is limited, and doesn't move humidity out of range.
+    #[test]
+    fn limits_correction() {
+        let mut compensator = LagCompensator::new(LagCompensation {
+            rate_smoothing: 1.0,
+            ..LagCompensation::default()
+        })
+        .unwrap();
+        compensator.update(at_humidity(40.0), 0);
+        assert_eq!(compensator.update(at_humidity(60.0), 1000).humidity, 70.0);
+
+        compensator.reset();
+        compensator.update(at_humidity(90.0), 0);
+        assert_eq!(compensator.update(at_humidity(98.0), 1000).humidity, 100.0);
+    }
+
+    /// Jitter is smoothed rather than amplified.
+    #[test]
+    fn limits_noise() {
+        let mut compensator = LagCompensator::new(LagCompensation::default()).unwrap();
+        for second in 0..50u64 {
+            let jitter = if second % 2 == 0 { 0.1 } else { -0.1 };
+            let estimate = compensator.update(at_humidity(50.0 + jitter), second * 1000);
+            assert!((estimate.humidity - 50.0).abs() < 1.0, "{estimate:?}");
+        }
+    }
+
+    /// Out of range settings are rejected.
+    #[test]
+    fn rejects_invalid_settings() {
+        let settings = LagCompensation {
+            humidity_tau_s: -1.0,
+            ..LagCompensation::default()
+        };
+        assert_eq!(LagCompensator::new(settings), Err(InvalidLagCompensation));
+        let settings = LagCompensation {
+            rate_smoothing: 0.0,
+            ..LagCompensation::default()
+        };
+        assert_eq!(LagCompensator::new(settings), Err(InvalidLagCompensation));
+    }
+}
@@ -6,7 +6,7 @@
 //!     # use embedded_hal_mock::eh1::delay::NoopDelay as MockDelay;
 //!     # use embedded_hal_mock::eh1::i2c::Mock as I2cMock;
 //!     # use embedded_hal_mock::eh1::i2c::Transaction;
-//!     # use aht20_driver::{AHT20, AHT20Initialized, Command, SENSOR_ADDRESS};
+//!     # use aht20_driver::{AHT20, Command, SENSOR_ADDRESS};
 //!     # let expectations = vec![
 //!     #     // check_status immediately succeeds, we don't need to send Initialize.
 //!     #     Transaction::read(SENSOR_ADDRESS, vec![0b0000_1000]),
@@ -40,14 +40,14 @@
 //!     # ];
 //!     # let mock_i2c = I2cMock::new(&expectations);
 //!     # let mut mock_delay = MockDelay::new();
-//!     let mut aht20_uninit = AHT20::new(mock_i2c, SENSOR_ADDRESS);
+//!     let aht20_uninit = AHT20::new(mock_i2c, SENSOR_ADDRESS);
 //!     let mut aht20 = aht20_uninit.init(&mut mock_delay).unwrap();
 //!     let measurement = aht20.measure(&mut mock_delay).unwrap();
 //!
 //!     println!("temperature (aht20): {:.2}C", measurement.temperature);
 //!     println!("humidity (aht20): {:.2}%", measurement.humidity);
 //!
-//!     aht20_uninit.destroy().done();
+//!     aht20.destroy().done();
 //!
 //! [AHT20 Datasheet](https://cdn-learn.adafruit.com/assets/assets/000/091/676/original/AHT20-datasheet-2020-4-16.pdf?1591047915)
 //!
@@ -113,10 +113,78 @@
 //!        Calc Humidity and Temp
 //! ```
 
+use core::marker::PhantomData;
+
 use crc_any::CRCu8;
 use embedded_hal::delay::DelayNs;
 use embedded_hal::i2c::I2c;
 
+mod adaptive;
+mod alarm;
+mod bucket;
+mod builder;
+mod cache;
+mod clock;
+mod comfort;
+mod condensation;
+mod continuous;
+mod control;
+mod degree_days;
+mod exposure;
+mod filter;
+mod history;
+mod lag;
+mod mould;
+mod oversample;
+mod probe;
+mod psychrometrics;
+mod retained;
+#[cfg(test)]
+mod test_support;
+mod trend;
+mod variant;
+mod ventilation;
+
+use adaptive::ConversionLearner;
+use builder::Config;
+use retained::Calibration;
+
+pub use adaptive::AdaptiveTiming;
+pub use alarm::{AlarmEvent, AlarmRule, Alarms, InvalidAlarmRule, Limit};
+pub use bucket::{BucketAggregator, BucketSummary, ZeroBucketLength};
+pub use builder::{AHT20Builder, BuildError, Compensation, ConfigError, PollMode, Validation};
+pub use cache::{AHT20Cached, CachedReading, DEFAULT_MIN_INTERVAL_MS};
+pub use clock::Clock;
+pub use comfort::{ChartPoint, ComfortAssessment, ComfortClass, ComfortZone, InvalidComfortZone};
+pub use condensation::{
+    CondensationAssessment, CondensationAssessmentNoFp, CondensationMonitor, CondensationRisk,
+    CondensationThresholds, InvalidCondensationThresholds, SurfaceTemperature,
+};
+pub use continuous::AHT20Continuous;
+pub use control::{
+    AHT20Controller, ControlDirection, ControlSettings, ControlStatus, ControllerError,
+    HysteresisControl, InvalidControlSettings, CONTROL_RETRY_BUDGET,
+};
+pub use degree_days::{
+    DailyDegreeDays, DegreeDayMethod, DegreeDaySettings, DegreeDayTotals, DegreeDays,
+    InvalidDegreeDaySettings,
+};
+pub use exposure::{Exposure, ExposureAccumulator, ExposureBand, InvalidExposureBand};
+pub use filter::{AHT20Filtered, Ema, Filter, InvalidFilter, Kalman, Median, MovingAverage};
+pub use history::{History, ReadingStatistics, Statistics, TimestampedReading};
+pub use lag::{InvalidLagCompensation, LagCompensation, LagCompensator};
+pub use mould::{InvalidMouldSettings, MouldGrowth, MouldSettings, SensitivityClass};
+pub use oversample::{Aggregation, OversampledReading};
+pub use probe::DetectedVariant;
+pub use psychrometrics::{absolute_humidity, dew_point, dew_point_no_fp, humidity_ratio};
+pub use retained::RetainedState;
+pub use trend::TrendEstimator;
+pub use variant::Variant;
+pub use ventilation::{
+    InvalidVentilationSettings, VentilationAdvice, VentilationAdvisor, VentilationAssessment,
+    VentilationSettings,
+};
+
 /// AHT20 sensor's I2C address.
 pub const SENSOR_ADDRESS: u8 = 0b0011_1000; // This is I2C address 0x38;
 
@@ -138,14 +206,45 @@ pub enum Command {
                              // Also see Section 5.5. This takes 20ms or less to complete.
 }
 
+/// Registers that the DHT20, AHT21, AHT25 and AM2301B want reset after power-on.
+///
+/// These come from the vendor's sample code, which resets them if the status byte doesn't have
+/// both bits 3 and 4 set. Each register is read back and written with `0xB0 | register`.
+const RESET_REGISTERS: [u8; 3] = [0x1B, 0x1C, 0x1E];
+
+/// Status bits that must both be set for the registers in `RESET_REGISTERS` to be considered
+/// initialized. Bit 3 is the calibration bit, bit 4 is undocumented.
+const REGISTERS_INITIALIZED: u8 = 0b0001_1000;
+
+/// How many times `PollMode::FullFrame` reads a ready frame again after a CRC mismatch, before
+/// giving up on the conversion with `Error::InvalidCrc`.
+const FULL_FRAME_CRC_POLLS: u32 = 2;
+
 /// Status byte meanings.
 ///
-/// Table 10, page 8 of the datasheet.
+/// Table 10, page 8 of the datasheet. The AHT20 datasheet only documents `Busy` and `Calibrated`,
+/// the other bits are described in the AHT30 datasheet.
 pub enum Status {
     Busy = 0b1000_0000, // Status bit for busy - 8th bit enabled. 1<<7, 0x80
     // 1 is Busy measuring. 0 is "Free in dormant state" or "ready".
+    Mode = 0b0110_0000, // Status bits for the working mode - 7th and 6th bits. 0x60.
+    // 00 is NOR (normal) mode, 01 is CYC (cycle) mode, 1x is CMD (command) mode.
     Calibrated = 0b0000_1000, // Status bit for calibrated - 4th bit enabled. 1<<3, 0x08.
-                              // 1 is Calibrated, 0 is uncalibrated. If 0, send Command::Initialize.
+    // 1 is Calibrated, 0 is uncalibrated. If 0, send Command::Initialize.
+    CrcFlag = 0b0000_0100, // Status bit for the AHT30's CRC flag - 3rd bit enabled. 1<<2, 0x04.
+    Reserved = 0b0001_0011, // The remaining bits, 5th, 2nd and 1st. 0x13.
+}
+
+/// The working mode reported in bits 6 and 5 of the status byte.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "use-defmt", derive(defmt::Format))]
+pub enum Mode {
+    /// NOR mode, the sensor measures when asked to. This is what the driver uses.
+    Normal,
+    /// CYC mode, the sensor measures continuously.
+    Cycle,
+    /// CMD mode, the sensor is accepting commands.
+    Command,
 }
 
 /// SensorStatus is the response from the sensor indicating if it is ready to read from, and if it
@@ -155,7 +254,7 @@ pub enum Status {
 /// during initialization, which is when the sensor caibrates itself, and during
 /// measure. During measure the sensor will report itself as busy (not ready)
 /// for a period of 80ms.
-#[derive(Debug, Clone, Copy)]
+#[derive(Clone, Copy, PartialEq, Eq)]
 pub struct SensorStatus(pub u8);
 
 impl SensorStatus {
@@ -180,6 +279,68 @@ impl SensorStatus {
         // The calibrated bit should be set.
         (self.0 & Status::Calibrated as u8) != 0
     }
+
+    /// The working mode the sensor reports it is in.
+    pub fn mode(self) -> Mode {
+        match (self.0 & Status::Mode as u8) >> 5 {
+            0b00 => Mode::Normal,
+            0b01 => Mode::Cycle,
+            _ => Mode::Command,
+        }
+    }
+
+    /// Check the CRC flag. This is only documented for the AHT30, other parts report this bit as
+    /// reserved.
+    pub fn crc_flag(self) -> bool {
+        (self.0 & Status::CrcFlag as u8) != 0
+    }
+
+    /// The bits that none of the datasheets give a meaning to, with all other bits masked off.
+    pub fn reserved_bits(self) -> u8 {
+        self.0 & Status::Reserved as u8
+    }
+}
+
+impl core::fmt::Debug for SensorStatus {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("SensorStatus")
+            .field("ready", &self.is_ready())
+            .field("calibrated", &self.is_calibrated())
+            .field("mode", &self.mode())
+            .field("crc_flag", &self.crc_flag())
+            .field(
+                "reserved_bits",
+                &format_args!("{:#04x}", self.reserved_bits()),
+            )
+            .finish()
+    }
+}
+
+#[cfg(feature = "use-defmt")]
+impl defmt::Format for SensorStatus {
+    fn format(&self, f: defmt::Formatter) {
+        defmt::write!(
+            f,
+            "SensorStatus {{ ready: {}, calibrated: {}, mode: {}, crc_flag: {}, reserved_bits: {=u8:#04x} }}",
+            self.is_ready(),
+            self.is_calibrated(),
+            self.mode(),
+            self.crc_flag(),
+            self.reserved_bits()
+        )
+    }
+}
+
+/// One of the quantities in a `SensorReading`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "use-defmt", derive(defmt::Format))]
+pub enum Quantity {
+    /// Temperature in degrees Celsius.
+    Temperature,
+    /// Relative humidity in percent.
+    Humidity,
+    /// Dew point in degrees Celsius, derived from temperature and humidity.
+    DewPoint,
 }
 
 /// SensorReading is a single reading from the AHT20 sensor.
@@ -187,19 +348,32 @@ impl SensorStatus {
 /// This is returned from the `measure` method. You get:
 /// * humidity in % Relative Humidity
 /// * temperature in degrees Celsius.
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct SensorReading {
     pub humidity: f32,
     pub temperature: f32,
 }
 
 impl SensorReading {
+    /// The value of `quantity` in this reading.
+    pub fn get(&self, quantity: Quantity) -> f32 {
+        match quantity {
+            Quantity::Temperature => self.temperature,
+            Quantity::Humidity => self.humidity,
+            Quantity::DewPoint => self.dew_point(),
+        }
+    }
+
     /// Create a SensorReading from the data returned by the sensor.
     ///
     /// This is done by the `measure` method.
     fn from_bytes(sensor_data: [u8; 5]) -> Self {
         let (humidity_val, temperature_val) = SensorReading::raw_from_bytes(sensor_data);
+        SensorReading::from_raw(humidity_val, temperature_val)
+    }
 
+    /// Create a SensorReading from the 20-bit raw humidity and temperature values.
+    fn from_raw(humidity_val: u32, temperature_val: u32) -> Self {
         // From section 6.1 "Relative humidity transformation" here is how we turn this into
         // a relative humidity percantage value.
         let humidity_percent = (humidity_val as f32) / ((1 << 20) as f32) * 100.0;
@@ -214,6 +388,16 @@ impl SensorReading {
         }
     }
 
+    /// Apply the compensation offsets to this reading.
+    ///
+    /// Humidity is kept within 0-100% after the offset is added.
+    fn compensated(self, compensation: Compensation) -> Self {
+        SensorReading {
+            humidity: (self.humidity + compensation.humidity_offset).clamp(0.0, 100.0),
+            temperature: self.temperature + compensation.temperature_offset,
+        }
+    }
+
     /// Identical to `from_bytes`, but doesn't use floating point math.
     ///
     /// This limits the precision to just integer values, but doesn't bring in floating point
@@ -290,6 +474,9 @@ pub enum Error<E> {
     /// I2C data gets corrupted and we receive "ready", then later the
     /// CRC-checked status byte correctly reports "busy" and we have to abort the measurement.
     UnexpectedBusy,
+    /// The sensor reported itself uncalibrated after the driver was resumed from a
+    /// `RetainedState`. The sensor needs `init` to be run again.
+    Uncalibrated,
     /// Errors such as overflowing the stack.
     Internal,
 }
@@ -300,6 +487,7 @@ impl<E> core::fmt::Display for Error<E> {
             Error::I2c(_e) => write!(f, "I2C error"),
             Error::InvalidCrc => write!(f, "invalid CRC error"),
             Error::UnexpectedBusy => write!(f, "unexpected busy error"),
+            Error::Uncalibrated => write!(f, "sensor uncalibrated error"),
             Error::Internal => write!(f, "internal ATH20 driver error"),
         }
     }
@@ -307,19 +495,178 @@ impl<E> core::fmt::Display for Error<E> {
 
 impl<E> core::error::Error for Error<E> where E: core::fmt::Debug {}
 
-/// An AHT20 sensor on the I2C bus `I`.
+/// A failed `init`, with the uninitialized driver so the I2C bus isn't lost.
+///
+/// Use `error` to see what went wrong, and `into_driver` to try `init` again or to `destroy` the
+/// driver and get the bus back. `?` turns it into the `Error` alone.
+pub struct InitError<I>
+where
+    I: I2c,
+{
+    driver: AHT20<I, Uninit>,
+    error: Error<I::Error>,
+}
+
+impl<I> InitError<I>
+where
+    I: I2c,
+{
+    /// What went wrong.
+    pub fn error(&self) -> &Error<I::Error> {
+        &self.error
+    }
+
+    /// The uninitialized driver, which still owns the bus.
+    pub fn into_driver(self) -> AHT20<I, Uninit> {
+        self.driver
+    }
+
+    /// The uninitialized driver, and what went wrong.
+    pub fn into_parts(self) -> (AHT20<I, Uninit>, Error<I::Error>) {
+        (self.driver, self.error)
+    }
+}
+
+impl<I> core::fmt::Debug for InitError<I>
+where
+    I: I2c,
+{
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("InitError")
+            .field("error", &self.error)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<I> core::fmt::Display for InitError<I>
+where
+    I: I2c,
+{
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> Result<(), core::fmt::Error> {
+        write!(f, "init failed: {}", self.error)
+    }
+}
+
+impl<I> core::error::Error for InitError<I> where I: I2c {}
+
+impl<I> From<InitError<I>> for Error<I::Error>
+where
+    I: I2c,
+{
+    fn from(init_error: InitError<I>) -> Self {
+        init_error.error
+    }
+}
+
+/// Typestate for an `AHT20` that hasn't been initialized yet.
+///
+/// In this state you can `init` or `probe` the sensor.
+pub struct Uninit;
+
+/// Typestate for an `AHT20` that has been initialized and is ready to measure.
+///
+/// In this state you can trigger a measurement with `.measure(&mut delay)`.
+pub struct Ready;
+
+/// An AHT20 sensor on the I2C bus `I`, in the state `S`.
 ///
 /// The address of the sensor will be `SENSOR_ADDRESS` from this package, unless there is some kind
 /// of special address translating hardware in use.
-pub struct AHT20<I>
+///
+/// The driver starts out as `AHT20<I, Uninit>`, and `init` turns it into an `AHT20<I, Ready>`.
+/// Both own the I2C bus, so the driver can be kept in a `static`, an RTIC resource, or a struct
+/// alongside other application state. `destroy` gives the bus back in either state.
+pub struct AHT20<I, S = Uninit>
 where
     I: I2c,
 {
     i2c: I,
-    address: u8,
+    config: Config,
+    calibration: Calibration,
+    learner: Option<ConversionLearner>,
+    state: PhantomData<S>,
 }
 
-impl<I> AHT20<I>
+impl<I, S> AHT20<I, S>
+where
+    I: I2c,
+{
+    /// The variant profile this driver was created with, including any timing overrides.
+    pub fn variant(&self) -> Variant {
+        self.config.variant
+    }
+
+    /// The I2C address of the sensor.
+    pub fn address(&self) -> u8 {
+        self.config.address
+    }
+
+    /// Move this driver into the state `T`, keeping the bus and configuration.
+    fn into_state<T>(self) -> AHT20<I, T> {
+        AHT20 {
+            i2c: self.i2c,
+            config: self.config,
+            calibration: self.calibration,
+            learner: self.learner,
+            state: PhantomData,
+        }
+    }
+
+    /// check_Status reads a status byte from the AHT20 sensor to check its status.
+    ///
+    /// The sensor can be calibrated or not, also busy generating a sensor measurement or ready.
+    /// This method returns the SensorStatus struct, which you can use to determine what the state
+    /// of the sensor is.
+    ///
+    /// NOTE: The documentation suggests that we send a CheckStatus (0x71) command, followed by a
+    ///       read. Experience (https://github.com/anglerud/aht20-driver/pull/10) indicates that we
+    ///       can create a hang writing that command, and that just reading a status byte works.
+    ///
+    /// This is used by both measure_once and init.
+    fn check_status(&mut self) -> Result<SensorStatus, Error<I::Error>> {
+        #[cfg(feature = "use-defmt")]
+        defmt::debug!("check_status: requesting a status check from sensor.");
+        let mut read_buffer = [0u8; 1];
+
+        self.i2c
+            .read(self.config.address, &mut read_buffer)
+            .map_err(Error::I2c)?;
+
+        let status_byte = read_buffer[0];
+        Ok(SensorStatus::new(status_byte))
+    }
+
+    /// Send the "Trigger Measurement" command to the sensor.
+    ///
+    /// This does not return anything, it only instructs the sensor to get the data ready. After
+    /// sending this command, you need to wait 80ms before attempting to read data back. See the
+    /// `measure_once` function and the flowchart at the top of this file.
+    fn send_trigger_measurement(&mut self) -> Result<(), Error<I::Error>> {
+        // TriggerMeasurement is 0b1010_1100. Equivalent to 0xAC: Section 5.3, page 8, Table 9
+        // This command takes two bytes of parameter:  0b00110011 (0x33), then 0b0000_0000 (0x00).
+        let command: [u8; 3] = [
+            Command::TriggerMeasurement as u8,
+            // Two parameters as described in the datasheet. There is no indication what these
+            // parameters mean, just that they should be provided. There is no returned value.
+            // To get the measurement, see [measure](measure).
+            0b0011_0011, // 0x33
+            0b0000_0000, // 0x00
+        ];
+
+        self.i2c
+            .write(self.config.address, &command)
+            .map_err(Error::I2c)?;
+
+        Ok(())
+    }
+
+    /// Destroys this driver and releases the I2C bus `I`.
+    pub fn destroy(self) -> I {
+        self.i2c
+    }
+}
+
+impl<I> AHT20<I, Uninit>
 where
     I: I2c,
 {
@@ -328,14 +675,31 @@ where
     /// This consumes the I2C bus `I`. Before you can get temperature and humidity measurements,
     /// you must call the `init` method which calibrates the sensor. The address will almost always
     /// be `SENSOR_ADDRESS` from this crate.
+    ///
+    /// This uses the `Variant::AHT20` timings, see `with_variant` for the other sensors in the
+    /// family.
     pub fn new(i2c: I, address: u8) -> Self {
-        AHT20 { i2c, address }
+        AHT20::with_variant(i2c, address, Variant::AHT20)
+    }
+
+    /// Initializes the driver for a specific member of the AHT20 family.
+    ///
+    /// This is identical to `new`, but uses the timings and start-up behaviour from `variant`,
+    /// for example `Variant::DHT20`. See `builder` for the remaining settings.
+    pub fn with_variant(i2c: I, address: u8, variant: Variant) -> Self {
+        AHT20 {
+            i2c,
+            config: Config::new(address, variant),
+            calibration: Calibration::Verified,
+            learner: None,
+            state: PhantomData,
+        }
     }
 
     /// Run the AHT20 init and calibration routines.
     ///
-    /// This must be called before any other methods except `check_status`. This method will take
-    /// *at least* 40ms to return.
+    /// This consumes the uninitialized driver and returns one that is ready to measure. This method
+    /// will take *at least* the variant's power-on delay to return, 40ms for the AHT20.
     ///
     /// ```text
     ///          Start (Power on)
@@ -352,46 +716,93 @@ where
     ///                 ▼
     ///                Yes
     /// ```
-    pub fn init(
+    ///
+    /// Variants with `requires_register_reset` set also reset registers 0x1B, 0x1C and 0x1E after
+    /// the first status read, if that status byte doesn't have bits 3 and 4 set.
+    ///
+    /// If the sensor can't be initialized, the returned `InitError` holds the driver, so the bus
+    /// can still be recovered with `destroy`.
+    pub fn init(self, delay: &mut impl DelayNs) -> Result<AHT20<I, Ready>, InitError<I>> {
+        let power_on_delay_ms = self.config.variant.power_on_delay_ms;
+        self.init_after(delay, power_on_delay_ms)
+    }
+
+    /// Run the AHT20 init and calibration routines, waiting only for what's left of the power-on
+    /// delay.
+    ///
+    /// This is identical to `init`, except that it only waits for as much of the variant's
+    /// power-on delay as hasn't already passed since `powered_on_at_ms`, according to `clock`.
+    /// After an MCU-only reset or a wake from sleep the sensor has often been powered for long
+    /// enough that no wait is needed at all. If `powered_on_at_ms` is `None` the full delay is
+    /// used, as the power-on time isn't known.
+    pub fn init_with_clock(
+        self,
+        delay: &mut impl DelayNs,
+        clock: &mut impl Clock,
+        powered_on_at_ms: Option<u64>,
+    ) -> Result<AHT20<I, Ready>, InitError<I>> {
+        let power_on_delay_ms = self.config.variant.power_on_delay_ms;
+        let remaining_ms = match powered_on_at_ms {
+            Some(powered_on_at_ms) => {
+                let elapsed_ms = clock.now_ms().saturating_sub(powered_on_at_ms);
+                u64::from(power_on_delay_ms).saturating_sub(elapsed_ms) as u32
+            }
+            None => power_on_delay_ms,
+        };
+        self.init_after(delay, remaining_ms)
+    }
+
+    /// The work of `init`, after waiting `power_on_wait_ms` for the sensor to power up.
+    fn init_after(
+        mut self,
+        delay: &mut impl DelayNs,
+        power_on_wait_ms: u32,
+    ) -> Result<AHT20<I, Ready>, InitError<I>> {
+        match self.initialize(delay, power_on_wait_ms) {
+            Ok(()) => Ok(self.into_state()),
+            Err(error) => Err(InitError {
+                driver: self,
+                error,
+            }),
+        }
+    }
+
+    /// Wait `power_on_wait_ms`, then initialize and calibrate the sensor.
+    fn initialize(
         &mut self,
         delay: &mut impl DelayNs,
-    ) -> Result<AHT20Initialized<I>, Error<I::Error>> {
-        delay.delay_ms(40);
+        power_on_wait_ms: u32,
+    ) -> Result<(), Error<I::Error>> {
+        if power_on_wait_ms > 0 {
+            delay.delay_ms(power_on_wait_ms);
+        }
 
-        while !self.check_status()?.is_calibrated() {
+        let mut status = self.check_status()?;
+        if self.config.variant.requires_register_reset
+            && (status.0 & REGISTERS_INITIALIZED) != REGISTERS_INITIALIZED
+        {
+            #[cfg(feature = "use-defmt")]
+            defmt::debug!("init: resetting registers.");
+            self.reset_registers(delay)?;
+            delay.delay_ms(self.config.variant.init_delay_ms);
+            status = self.check_status()?;
+        }
+
+        while !status.is_calibrated() {
             self.send_initialize()?;
             #[cfg(feature = "use-defmt")]
-            defmt::debug!("init: waiting for sensor to report being calibrated, 10ms.");
-            delay.delay_ms(10);
+            defmt::debug!(
+                "init: waiting for sensor to report being calibrated, {}ms.",
+                self.config.variant.init_delay_ms
+            );
+            delay.delay_ms(self.config.variant.init_delay_ms);
+            status = self.check_status()?;
         }
 
         #[cfg(feature = "use-defmt")]
         defmt::debug!("init: sensor reporting being calibrated, init done.");
-        Ok(AHT20Initialized { aht20: self })
-    }
-
-    /// check_Status reads a status byte from the AHT20 sensor to check its status.
-    ///
-    /// The sensor can be calibrated or not, also busy generating a sensor measurement or ready.
-    /// This method returns the SensorStatus struct, which you can use to determine what the state
-    /// of the sensor is.
-    ///
-    /// NOTE: The documentation suggests that we send a CheckStatus (0x71) command, followed by a
-    ///       read. Experience (https://github.com/anglerud/aht20-driver/pull/10) indicates that we
-    ///       can create a hang writing that command, and that just reading a status byte works.
-    ///
-    /// This is used by both measure_once and init.
-    fn check_status(&mut self) -> Result<SensorStatus, Error<I::Error>> {
-        #[cfg(feature = "use-defmt")]
-        defmt::debug!("check_status: requesting a status check from sensor.");
-        let mut read_buffer = [0u8; 1];
-
-        self.i2c
-            .read(self.address, &mut read_buffer)
-            .map_err(Error::I2c)?;
-
-        let status_byte = read_buffer[0];
-        Ok(SensorStatus::new(status_byte))
+        self.calibration = Calibration::Verified;
+        Ok(())
     }
 
     /// send_initialize sends the Initialize command to the sensor which make it calibrate.
@@ -411,28 +822,44 @@ where
             0b0000_0000, // 0x00
         ];
 
-        self.i2c.write(self.address, &command).map_err(Error::I2c)?;
+        self.i2c
+            .write(self.config.address, &command)
+            .map_err(Error::I2c)?;
 
         Ok(())
     }
 
-    /// Destroys this driver and releases the I2C bus `I`
-    pub fn destroy(self) -> I {
-        self.i2c
-    }
-}
+    /// reset_registers resets the registers listed in `RESET_REGISTERS`.
+    ///
+    /// This follows the vendor's sample code for the DHT20, AHT21 and AHT25: write the register
+    /// address followed by two zero bytes, wait 5ms, read three bytes back, wait 10ms, and then
+    /// write the last two of those bytes back to register `0xB0 | register`.
+    fn reset_registers(&mut self, delay: &mut impl DelayNs) -> Result<(), Error<I::Error>> {
+        for register in RESET_REGISTERS {
+            self.i2c
+                .write(self.config.address, &[register, 0x00, 0x00])
+                .map_err(Error::I2c)?;
+            delay.delay_ms(5);
+
+            let mut read_buffer = [0u8; 3];
+            self.i2c
+                .read(self.config.address, &mut read_buffer)
+                .map_err(Error::I2c)?;
+            delay.delay_ms(10);
 
-/// AHT20Initialized is returned by AHT20::init() and the sensor is ready to read from.
-///
-/// In this state you can trigger a measurement with `.measure(&mut delay)`.
-pub struct AHT20Initialized<'a, I>
-where
-    I: I2c,
-{
-    aht20: &'a mut AHT20<I>,
+            self.i2c
+                .write(
+                    self.config.address,
+                    &[0xB0 | register, read_buffer[1], read_buffer[2]],
+                )
+                .map_err(Error::I2c)?;
+        }
+
+        Ok(())
+    }
 }
 
-impl<'a, I> AHT20Initialized<'a, I>
+impl<I> AHT20<I, Ready>
 where
     I: I2c,
 {
@@ -478,25 +905,8 @@ where
     ///        Calc Humidity and Temp
     /// ```
     pub fn measure(&mut self, delay: &mut impl DelayNs) -> Result<SensorReading, Error<I::Error>> {
-        loop {
-            let measurement_result = self.measure_once(delay);
-            match measurement_result {
-                Ok(sb) => {
-                    return Ok(SensorReading::from_bytes(sb));
-                }
-                Err(Error::InvalidCrc) => {
-                    // CRC failed to validate, we'll go back and issue another read request.
-                    #[cfg(feature = "use-defmt")]
-                    defmt::error!("Invalid CRC, retrying.");
-                }
-                Err(Error::UnexpectedBusy) => {
-                    // Possibly indicates the previously seen 'ready' was due to uncorrected noise.
-                    #[cfg(feature = "use-defmt")]
-                    defmt::error!("Sensor contradicted a ready status with a crc-checked busy.");
-                }
-                Err(other) => return Err(other),
-            }
-        }
+        let sensor_data = self.measure_retrying(delay)?;
+        Ok(SensorReading::from_bytes(sensor_data).compensated(self.config.compensation))
     }
 
     /// This is identical to `measure`, except it doesn't use floating point math.
@@ -505,101 +915,208 @@ where
     /// to bring in floating point math functions, which can take up a lot of space, and
     /// might be slow.
     ///
-    /// The drawback is that precision is limited to only integer values.
+    /// The drawback is that precision is limited to only integer values. Any `Compensation`
+    /// configured with the builder is not applied.
     pub fn measure_no_fp(
         &mut self,
         delay: &mut impl DelayNs,
     ) -> Result<SensorReading, Error<I::Error>> {
-        // TODO: See if we can refactor here, the only difference is from_bytes and
-        //       from_bytes_no_fp.
+        let sensor_data = self.measure_retrying(delay)?;
+        Ok(SensorReading::from_bytes_no_fp(sensor_data))
+    }
+
+    /// Call `measure_once` until it succeeds, or the retry budget runs out.
+    ///
+    /// Only `InvalidCrc` and `UnexpectedBusy` are retried, other errors are returned at once.
+    fn measure_retrying(&mut self, delay: &mut impl DelayNs) -> Result<[u8; 5], Error<I::Error>> {
+        let mut retries: u8 = 0;
         loop {
-            let measurement_result = self.measure_once(delay);
-            match measurement_result {
-                Ok(sb) => {
-                    return Ok(SensorReading::from_bytes_no_fp(sb));
-                }
+            let error = match self.measure_once(delay) {
+                Ok(sensor_data) => return Ok(sensor_data),
                 Err(Error::InvalidCrc) => {
                     // CRC failed to validate, we'll go back and issue another read request.
                     #[cfg(feature = "use-defmt")]
                     defmt::error!("Invalid CRC, retrying.");
+                    Error::InvalidCrc
                 }
                 Err(Error::UnexpectedBusy) => {
                     // Possibly indicates the previously seen 'ready' was due to uncorrected noise.
                     #[cfg(feature = "use-defmt")]
                     defmt::error!("Sensor contradicted a ready status with a crc-checked busy.");
+                    self.distrust_learned_wait();
+                    Error::UnexpectedBusy
                 }
                 Err(other) => return Err(other),
+            };
+
+            if let Some(budget) = self.config.retry_budget {
+                if retries >= budget {
+                    return Err(error);
+                }
             }
+            retries = retries.saturating_add(1);
+        }
+    }
+
+    /// Go back to the datasheet wait after an `UnexpectedBusy`, as a learned conversion time
+    /// might have been too short.
+    fn distrust_learned_wait(&mut self) {
+        if let Some(learner) = self.learner.as_mut() {
+            learner.fall_back();
         }
     }
 
     /// Perform one measurement and return the sensor's 5 raw data bytes.
     ///
-    /// This takes at least 80ms to complete, and only returns 2x20 bits in 5 bytes.
+    /// This takes at least the variant's measurement delay (80ms for the AHT20) to complete, and
+    /// only returns 2x20 bits in 5 bytes.
     /// This data is interpreted by the `measure` function.
     fn measure_once(&mut self, delay: &mut impl DelayNs) -> Result<[u8; 5], Error<I::Error>> {
+        if self.calibration == Calibration::Lost {
+            return Err(Error::Uncalibrated);
+        }
+
+        let wait_ms = self.measurement_wait_ms();
         self.send_trigger_measurement()?;
-        delay.delay_ms(80);
+        delay.delay_ms(wait_ms);
 
-        // Wait for measurement to be ready
-        while !self.aht20.check_status()?.is_ready() {
-            #[cfg(feature = "use-defmt")]
-            defmt::debug!("measure_once: waiting for ready, 1ms.");
-            delay.delay_ms(1);
+        let (sensor_data, polls) = self.read_triggered(delay)?;
+
+        if let Some(learner) = self.learner.as_mut() {
+            learner.record(wait_ms, polls, self.config.variant.poll_interval_ms);
         }
 
-        // 1 byte status, 20 bits humidity + 20 bits temperature, 1 byte CRC
-        let mut read_buffer = [0u8; 7];
-        self.aht20
-            .i2c
-            .read(self.aht20.address, &mut read_buffer)
-            .map_err(Error::I2c)?;
+        Ok(sensor_data)
+    }
+
+    /// Wait for an already triggered measurement to be ready, read it and check it.
+    ///
+    /// Returns the 5 raw data bytes, and how many times the sensor was polled while busy.
+    fn read_triggered(
+        &mut self,
+        delay: &mut impl DelayNs,
+    ) -> Result<([u8; 5], u32), Error<I::Error>> {
+        // Wait for measurement to be ready
+        let (read_buffer, polls) = match self.config.poll_mode {
+            PollMode::StatusByte => self.poll_status_byte(delay)?,
+            PollMode::FullFrame => self.poll_full_frame(delay)?,
+        };
 
         let data: &[u8] = &read_buffer[..6];
         let crc_byte: u8 = read_buffer[6];
 
-        let crc = compute_crc(data);
-        if crc_byte != crc {
-            return Err(Error::InvalidCrc);
+        let validation = self.config.validation;
+        if validation != Validation::None {
+            let crc = compute_crc(data);
+            if crc_byte != crc {
+                return Err(Error::InvalidCrc);
+            }
         }
 
         // The first byte of the sensor's response is a repeat of the status byte.
         // There is a minescule chance that the previous ready message was caused
         // by noise on the i2c bus. This byte has been CRC-checked.
         let status = SensorStatus::new(read_buffer[0]);
-        if !status.is_ready() {
+        if validation == Validation::Strict && !status.is_ready() {
             return Err(Error::UnexpectedBusy);
         }
 
+        // A driver resumed from a RetainedState hasn't seen the calibration bit yet.
+        if self.calibration == Calibration::Unverified {
+            if !status.is_calibrated() {
+                #[cfg(feature = "use-defmt")]
+                defmt::warn!("measure_once: resumed sensor reports being uncalibrated.");
+                self.calibration = Calibration::Lost;
+                return Err(Error::Uncalibrated);
+            }
+            self.calibration = Calibration::Verified;
+        }
+
         // Arrays implement TryFrom for slices. In case the length of the slice does not match
         // the requested array - it will return a TryFromSliceError, but we are selecting the
         // right number of bytes so there is no risk. Mapping to a generic error.
-        data[1..6].try_into().map_err(|_| Error::Internal)
+        let sensor_data = data[1..6].try_into().map_err(|_| Error::Internal)?;
+        Ok((sensor_data, polls))
     }
 
-    /// Send the "Trigger Measurement" command to the sensor.
+    /// Poll the status byte until the sensor is ready, then read the 7 byte frame.
     ///
-    /// This does not return anything, it only instructs the sensor to get the data ready. After
-    /// sending this command, you need to wait 80ms before attempting to read data back. See the
-    /// `measure_once` function and the flowchart at the top of this file.
-    fn send_trigger_measurement(&mut self) -> Result<(), Error<I::Error>> {
-        // TriggerMeasurement is 0b1010_1100. Equivalent to 0xAC: Section 5.3, page 8, Table 9
-        // This command takes two bytes of parameter:  0b00110011 (0x33), then 0b0000_0000 (0x00).
-        let command: [u8; 3] = [
-            Command::TriggerMeasurement as u8,
-            // Two parameters as described in the datasheet. There is no indication what these
-            // parameters mean, just that they should be provided. There is no returned value.
-            // To get the measurement, see [measure](measure).
-            0b0011_0011, // 0x33
-            0b0000_0000, // 0x00
-        ];
+    /// Returns the frame, and how many times the sensor was found busy.
+    fn poll_status_byte(
+        &mut self,
+        delay: &mut impl DelayNs,
+    ) -> Result<([u8; 7], u32), Error<I::Error>> {
+        let mut polls: u32 = 0;
+        while !self.check_status()?.is_ready() {
+            #[cfg(feature = "use-defmt")]
+            defmt::debug!(
+                "measure_once: waiting for ready, {}ms.",
+                self.config.variant.poll_interval_ms
+            );
+            delay.delay_ms(self.config.variant.poll_interval_ms);
+            polls = polls.saturating_add(1);
+        }
 
-        self.aht20
-            .i2c
-            .write(self.aht20.address, &command)
+        // 1 byte status, 20 bits humidity + 20 bits temperature, 1 byte CRC
+        let mut read_buffer = [0u8; 7];
+        self.i2c
+            .read(self.config.address, &mut read_buffer)
             .map_err(Error::I2c)?;
 
-        Ok(())
+        Ok((read_buffer, polls))
+    }
+
+    /// Read the whole 7 byte frame until its status byte says the sensor is ready and, unless
+    /// validation is off, the CRC matches.
+    ///
+    /// A ready frame with a bad CRC is read again up to `FULL_FRAME_CRC_POLLS` times, as the
+    /// conversion is finished and the next read will usually be clean. Returns the frame, and how
+    /// many times the sensor was found busy.
+    fn poll_full_frame(
+        &mut self,
+        delay: &mut impl DelayNs,
+    ) -> Result<([u8; 7], u32), Error<I::Error>> {
+        let mut polls: u32 = 0;
+        let mut crc_polls: u32 = 0;
+        let mut read_buffer = [0u8; 7];
+        loop {
+            self.i2c
+                .read(self.config.address, &mut read_buffer)
+                .map_err(Error::I2c)?;
+            if SensorStatus::new(read_buffer[0]).is_ready() {
+                let crc_ok = self.config.validation == Validation::None
+                    || compute_crc(&read_buffer[..6]) == read_buffer[6];
+                if crc_ok {
+                    return Ok((read_buffer, polls));
+                }
+                if crc_polls >= FULL_FRAME_CRC_POLLS {
+                    return Err(Error::InvalidCrc);
+                }
+                #[cfg(feature = "use-defmt")]
+                defmt::debug!("measure_once: ready frame with a bad CRC, reading it again.");
+                crc_polls += 1;
+            } else {
+                #[cfg(feature = "use-defmt")]
+                defmt::debug!(
+                    "measure_once: waiting for ready frame, {}ms.",
+                    self.config.variant.poll_interval_ms
+                );
+                polls = polls.saturating_add(1);
+            }
+            delay.delay_ms(self.config.variant.poll_interval_ms);
+        }
+    }
+
+    /// How long the next measurement will wait before polling the sensor, in milliseconds.
+    ///
+    /// This is the variant's measurement delay, unless adaptive timing was turned on with
+    /// `AHT20Builder::adaptive_timing`, in which case it is the currently learned wait.
+    pub fn measurement_wait_ms(&self) -> u32 {
+        let datasheet_ms = self.config.variant.measurement_delay_ms;
+        match &self.learner {
+            Some(learner) => learner.initial_wait_ms(datasheet_ms),
+            None => datasheet_ms,
+        }
     }
 
     /// Send the Soft Reset command to the sensor.
@@ -610,9 +1127,8 @@ where
         // SoftReset is 0b1011_1010. Equivalent to 0xBA, Section 5.3, page 8, Table 9.
         let command: [u8; 1] = [Command::SoftReset as u8];
 
-        self.aht20
-            .i2c
-            .write(self.aht20.address, &command)
+        self.i2c
+            .write(self.config.address, &command)
             .map_err(Error::I2c)?;
         // The datasheet in section 5.5 says there is a guarantee that the reset time does
         // not exceed 20ms. We wait the full 20ms to ensure you can trigger a measurement
@@ -622,9 +1138,64 @@ where
         Ok(())
     }
 
-    /// Destroys this initialized driver and lets you release the I2C bus `I`
-    pub fn destroy(self) -> &'a mut AHT20<I> {
-        self.aht20
+    /// Attach a delay to this driver, so it doesn't need to be passed to every call.
+    ///
+    /// See `AHT20WithDelay`.
+    pub fn with_delay<D>(self, delay: D) -> AHT20WithDelay<I, D>
+    where
+        D: DelayNs,
+    {
+        AHT20WithDelay { aht20: self, delay }
+    }
+}
+
+/// An initialized AHT20 that holds on to its own delay `D`.
+///
+/// This is returned by `AHT20::with_delay`, and has the same measuring methods as
+/// `AHT20<I, Ready>` but without the delay parameter. Use `parts_mut` to get at methods that
+/// aren't repeated here.
+pub struct AHT20WithDelay<I, D>
+where
+    I: I2c,
+    D: DelayNs,
+{
+    aht20: AHT20<I, Ready>,
+    delay: D,
+}
+
+impl<I, D> AHT20WithDelay<I, D>
+where
+    I: I2c,
+    D: DelayNs,
+{
+    /// Measure temperature and humidity. See `AHT20::measure`.
+    pub fn measure(&mut self) -> Result<SensorReading, Error<I::Error>> {
+        self.aht20.measure(&mut self.delay)
+    }
+
+    /// Measure temperature and humidity without floating point math. See `AHT20::measure_no_fp`.
+    pub fn measure_no_fp(&mut self) -> Result<SensorReading, Error<I::Error>> {
+        self.aht20.measure_no_fp(&mut self.delay)
+    }
+
+    /// Send the Soft Reset command to the sensor. See `AHT20::soft_reset`.
+    pub fn soft_reset(&mut self) -> Result<(), Error<I::Error>> {
+        self.aht20.soft_reset(&mut self.delay)
+    }
+
+    /// Borrow the driver and the delay separately, to call driver methods that take a delay.
+    pub fn parts_mut(&mut self) -> (&mut AHT20<I, Ready>, &mut D) {
+        (&mut self.aht20, &mut self.delay)
+    }
+
+    /// Separate the driver from the delay again.
+    pub fn release(self) -> (AHT20<I, Ready>, D) {
+        (self.aht20, self.delay)
+    }
+
+    /// Destroys this driver and releases the I2C bus `I` and the delay `D`.
+    pub fn destroy(self) -> (I, D) {
+        (self.aht20.destroy(), self.delay)
     }
 }
 
@@ -665,11 +1236,19 @@ fn compute_crc(bytes: &[u8]) -> u8 {
 
 #[cfg(test)]
 mod tests {
-    use super::{AHT20Initialized, Error, AHT20, SENSOR_ADDRESS};
+    use super::{Error, Ready, Variant, AHT20, SENSOR_ADDRESS};
+    use crate::test_support::{measurement, measurement_frame, trigger_measurement};
+    use embedded_hal::i2c::ErrorKind;
     use embedded_hal_mock::eh1::delay::NoopDelay as MockDelay;
+    use embedded_hal_mock::eh1::delay::{CheckedDelay, Transaction as DelayTransaction};
     use embedded_hal_mock::eh1::i2c::Mock as I2cMock;
     use embedded_hal_mock::eh1::i2c::Transaction;
 
+    /// Create a driver in the `Ready` state without going through `init`.
+    fn ready<I: embedded_hal::i2c::I2c>(i2c: I) -> AHT20<I, Ready> {
+        AHT20::new(i2c, SENSOR_ADDRESS).into_state()
+    }
+
     /// Test SensorStatus reporting being ready.
     #[test]
     fn sensorstatus_is_ready() {
@@ -702,6 +1281,50 @@ mod tests {
         assert!(!status.is_calibrated());
     }
 
+    /// Test SensorStatus decoding the working mode bits.
+    #[test]
+    fn sensorstatus_mode() {
+        assert_eq!(
+            super::SensorStatus::new(0b0000_0000).mode(),
+            super::Mode::Normal
+        );
+        assert_eq!(
+            super::SensorStatus::new(0b0010_0000).mode(),
+            super::Mode::Cycle
+        );
+        assert_eq!(
+            super::SensorStatus::new(0b0100_0000).mode(),
+            super::Mode::Command
+        );
+        assert_eq!(
+            super::SensorStatus::new(0b0110_0000).mode(),
+            super::Mode::Command
+        );
+    }
+
+    /// Test SensorStatus decoding the AHT30 CRC flag and the reserved bits.
+    #[test]
+    fn sensorstatus_crc_flag_and_reserved_bits() {
+        let status = super::SensorStatus::new(0b0001_1100);
+        assert!(status.crc_flag());
+        assert_eq!(status.reserved_bits(), 0b0001_0000);
+
+        let status = super::SensorStatus::new(0b1110_1011);
+        assert!(!status.crc_flag());
+        assert_eq!(status.reserved_bits(), 0b0000_0011);
+    }
+
+    /// Test that the Debug output of SensorStatus names the bits.
+    #[test]
+    fn sensorstatus_debug() {
+        let status = super::SensorStatus::new(0b0001_1100);
+        assert_eq!(
+            format!("{:?}", status),
+            "SensorStatus { ready: true, calibrated: true, mode: Normal, crc_flag: true, \
+             reserved_bits: 0x10 }"
+        );
+    }
+
     /// Test creating new AHT20 sensors.
     ///
     /// Test that we can create multiple AHT20 devices. We test this because it's one of the
@@ -769,8 +1392,8 @@ mod tests {
         let mock_i2c = I2cMock::new(&expectations);
         let mut mock_delay = MockDelay::new();
 
-        let mut aht20 = AHT20::new(mock_i2c, SENSOR_ADDRESS);
-        aht20.init(&mut mock_delay).unwrap();
+        let aht20 = AHT20::new(mock_i2c, SENSOR_ADDRESS);
+        let aht20 = aht20.init(&mut mock_delay).unwrap();
 
         let mut mock = aht20.destroy();
         mock.done(); // verify expectations
@@ -804,13 +1427,140 @@ mod tests {
         let mock_i2c = I2cMock::new(&expectations);
         let mut mock_delay = MockDelay::new();
 
-        let mut aht20 = AHT20::new(mock_i2c, SENSOR_ADDRESS);
-        aht20.init(&mut mock_delay).unwrap();
+        let aht20 = AHT20::new(mock_i2c, SENSOR_ADDRESS);
+        let aht20 = aht20.init(&mut mock_delay).unwrap();
+
+        let mut mock = aht20.destroy();
+        mock.done(); // verify expectations
+    }
+
+    /// A failed init gives the driver back, so the bus can be recovered or init retried.
+    #[test]
+    fn init_failure_returns_driver() {
+        let expectations = vec![
+            Transaction::read(SENSOR_ADDRESS, vec![0]).with_error(ErrorKind::ArbitrationLoss),
+            // The retry finds a calibrated sensor.
+            Transaction::read(SENSOR_ADDRESS, vec![0b0000_1000]),
+        ];
+        let mock_i2c = I2cMock::new(&expectations);
+        let mut mock_delay = MockDelay::new();
+
+        let aht20 = AHT20::new(mock_i2c, SENSOR_ADDRESS);
+        let Err(init_error) = aht20.init(&mut mock_delay) else {
+            panic!("init succeeded despite the bus error");
+        };
+        assert_eq!(init_error.error(), &Error::I2c(ErrorKind::ArbitrationLoss));
+
+        let aht20 = init_error.into_driver().init(&mut mock_delay).unwrap();
+        let mut mock = aht20.destroy();
+        mock.done(); // verify expectations
+    }
+
+    /// Initialize a DHT20 whose registers need resetting.
+    ///
+    /// The status byte doesn't have bits 3 and 4 set, so the three registers are reset before the
+    /// calibration check.
+    #[test]
+    fn init_dht20_with_register_reset() {
+        let mut expectations = vec![
+            // Neither bit 3 (calibrated) nor bit 4 set.
+            Transaction::read(SENSOR_ADDRESS, vec![0b0000_0000]),
+        ];
+        for register in super::RESET_REGISTERS {
+            expectations.push(Transaction::write(
+                SENSOR_ADDRESS,
+                vec![register, 0x00, 0x00],
+            ));
+            expectations.push(Transaction::read(SENSOR_ADDRESS, vec![0x00, 0x12, 0x34]));
+            // The last two bytes read get written back.
+            expectations.push(Transaction::write(
+                SENSOR_ADDRESS,
+                vec![0xB0 | register, 0x12, 0x34],
+            ));
+        }
+        // After the reset, the sensor reports both bits set.
+        expectations.push(Transaction::read(SENSOR_ADDRESS, vec![0b0001_1000]));
+        let mock_i2c = I2cMock::new(&expectations);
+        let mut mock_delay = MockDelay::new();
+
+        let aht20 = AHT20::with_variant(mock_i2c, SENSOR_ADDRESS, Variant::DHT20);
+        let aht20 = aht20.init(&mut mock_delay).unwrap();
 
         let mut mock = aht20.destroy();
         mock.done(); // verify expectations
     }
 
+    /// Initialize a DHT20 whose registers are already initialized.
+    ///
+    /// With bits 3 and 4 set in the status byte, no register reset is done.
+    #[test]
+    fn init_dht20_without_register_reset() {
+        let expectations = vec![Transaction::read(SENSOR_ADDRESS, vec![0b0001_1000])];
+        let mock_i2c = I2cMock::new(&expectations);
+        let mut mock_delay = MockDelay::new();
+
+        let aht20 = AHT20::with_variant(mock_i2c, SENSOR_ADDRESS, Variant::DHT20);
+        let aht20 = aht20.init(&mut mock_delay).unwrap();
+
+        let mut mock = aht20.destroy();
+        mock.done(); // verify expectations
+    }
+
+    /// Initialize with a clock, long after the sensor was powered on.
+    ///
+    /// No power-on wait is needed, so the delay is never called.
+    #[test]
+    fn init_with_clock_powered_long_ago() {
+        let expectations = vec![Transaction::read(SENSOR_ADDRESS, vec![0b0000_1000])];
+        let mock_i2c = I2cMock::new(&expectations);
+        let mut mock_delay = CheckedDelay::new(&[]);
+        let mut clock = || 5_000;
+
+        let aht20 = AHT20::new(mock_i2c, SENSOR_ADDRESS);
+        let aht20 = aht20
+            .init_with_clock(&mut mock_delay, &mut clock, Some(1_000))
+            .unwrap();
+
+        aht20.destroy().done();
+        mock_delay.done();
+    }
+
+    /// Initialize with a clock, shortly after the sensor was powered on.
+    ///
+    /// Only the rest of the 40ms power-on wait is done.
+    #[test]
+    fn init_with_clock_powered_recently() {
+        let expectations = vec![Transaction::read(SENSOR_ADDRESS, vec![0b0000_1000])];
+        let mock_i2c = I2cMock::new(&expectations);
+        let mut mock_delay = CheckedDelay::new(&[DelayTransaction::delay_ms(25)]);
+        let mut clock = || 1_015;
+
+        let aht20 = AHT20::new(mock_i2c, SENSOR_ADDRESS);
+        let aht20 = aht20
+            .init_with_clock(&mut mock_delay, &mut clock, Some(1_000))
+            .unwrap();
+
+        aht20.destroy().done();
+        mock_delay.done();
+    }
+
+    /// Initialize with a clock, but without knowing when the sensor was powered on.
+    #[test]
+    fn init_with_clock_unknown_power_on() {
+        let expectations = vec![Transaction::read(SENSOR_ADDRESS, vec![0b0000_1000])];
+        let mock_i2c = I2cMock::new(&expectations);
+        let mut mock_delay = CheckedDelay::new(&[DelayTransaction::delay_ms(40)]);
+        let mut clock = || 1_015;
+
+        let aht20 = AHT20::new(mock_i2c, SENSOR_ADDRESS);
+        let aht20 = aht20
+            .init_with_clock(&mut mock_delay, &mut clock, None)
+            .unwrap();
+
+        aht20.destroy().done();
+        mock_delay.done();
+    }
+
     /// Test sending the i2c SoftReset command.
     #[test]
     fn soft_reset() {
@@ -821,9 +1571,8 @@ mod tests {
         let mock_i2c = I2cMock::new(&expectations);
         let mut mock_delay = MockDelay::new();
 
-        let mut aht20 = AHT20::new(mock_i2c, SENSOR_ADDRESS);
-        let mut aht20_init = AHT20Initialized { aht20: &mut aht20 };
-        aht20_init.soft_reset(&mut mock_delay).unwrap();
+        let mut aht20 = ready(mock_i2c);
+        aht20.soft_reset(&mut mock_delay).unwrap();
 
         let mut mock = aht20.destroy();
         mock.done(); // verify expectations
@@ -843,8 +1592,7 @@ mod tests {
         let mock_i2c = I2cMock::new(&expectations);
 
         let mut aht20 = AHT20::new(mock_i2c, SENSOR_ADDRESS);
-        let mut aht20_init = AHT20Initialized { aht20: &mut aht20 };
-        aht20_init.send_trigger_measurement().unwrap();
+        aht20.send_trigger_measurement().unwrap();
 
         let mut mock = aht20.destroy();
         mock.done(); // verify expectations
@@ -888,9 +1636,8 @@ mod tests {
         let mock_i2c = I2cMock::new(&expectations);
         let mut mock_delay = MockDelay::new();
 
-        let mut aht20 = AHT20::new(mock_i2c, SENSOR_ADDRESS);
-        let mut aht20_init = AHT20Initialized { aht20: &mut aht20 };
-        aht20_init.measure_once(&mut mock_delay).unwrap();
+        let mut aht20 = ready(mock_i2c);
+        aht20.measure_once(&mut mock_delay).unwrap();
 
         let mut mock = aht20.destroy();
         mock.done(); // verify expectations
@@ -937,12 +1684,11 @@ mod tests {
         let mock_i2c = I2cMock::new(&expectations);
         let mut mock_delay = MockDelay::new();
 
-        let mut aht20 = AHT20::new(mock_i2c, SENSOR_ADDRESS);
-        let mut aht20_init = AHT20Initialized { aht20: &mut aht20 };
+        let mut aht20 = ready(mock_i2c);
         // We received a ready from the check_status method, then a busy in the CRC-checked
         // status byte - and therefore we got the UnexpectedBusy.
         assert_eq!(
-            aht20_init.measure_once(&mut mock_delay),
+            aht20.measure_once(&mut mock_delay),
             Err(Error::UnexpectedBusy)
         );
 
@@ -991,9 +1737,8 @@ mod tests {
         let mock_i2c = I2cMock::new(&expectations);
         let mut mock_delay = MockDelay::new();
 
-        let mut aht20 = AHT20::new(mock_i2c, SENSOR_ADDRESS);
-        let mut aht20_init = AHT20Initialized { aht20: &mut aht20 };
-        aht20_init.measure_once(&mut mock_delay).unwrap();
+        let mut aht20 = ready(mock_i2c);
+        aht20.measure_once(&mut mock_delay).unwrap();
 
         let mut mock = aht20.destroy();
         mock.done(); // verify expectations
@@ -1039,9 +1784,8 @@ mod tests {
         let mut mock_delay = MockDelay::new();
 
         // test and verify
-        let mut aht20 = AHT20::new(mock_i2c, SENSOR_ADDRESS);
-        let mut aht20_init = AHT20Initialized { aht20: &mut aht20 };
-        match aht20_init.measure_once(&mut mock_delay) {
+        let mut aht20 = ready(mock_i2c);
+        match aht20.measure_once(&mut mock_delay) {
             Ok(_) => panic!("CRC is wrong and measure_once should not pass."),
             Err(err_type) => assert_eq!(err_type, Error::InvalidCrc),
         }
@@ -1089,9 +1833,8 @@ mod tests {
         let mut mock_delay = MockDelay::new();
 
         // test
-        let mut aht20 = AHT20::new(mock_i2c, SENSOR_ADDRESS);
-        let mut aht20_init = AHT20Initialized { aht20: &mut aht20 };
-        let measurement = aht20_init.measure(&mut mock_delay).unwrap();
+        let mut aht20 = ready(mock_i2c);
+        let measurement = aht20.measure(&mut mock_delay).unwrap();
 
         // verification
         let mut mock = aht20.destroy();
@@ -1142,9 +1885,8 @@ mod tests {
         let mut mock_delay = MockDelay::new();
 
         // test
-        let mut aht20 = AHT20::new(mock_i2c, SENSOR_ADDRESS);
-        let mut aht20_init = AHT20Initialized { aht20: &mut aht20 };
-        let measurement = aht20_init.measure_no_fp(&mut mock_delay).unwrap();
+        let mut aht20 = ready(mock_i2c);
+        let measurement = aht20.measure_no_fp(&mut mock_delay).unwrap();
 
         // verification
         let mut mock = aht20.destroy();
@@ -1158,7 +1900,207 @@ mod tests {
         assert!(measurement.humidity == 39.0);
     }
 
-    /// Test a valid CRC invocation.
+    /// Test a measurement with the delay held by the driver.
+    #[test]
+    fn measure_with_held_delay() {
+        let expectations = measurement(measurement_frame());
+        let mock_i2c = I2cMock::new(&expectations);
+
+        let mut aht20 = ready(mock_i2c).with_delay(MockDelay::new());
+        let measurement = aht20.measure().unwrap();
+
+        let (mut mock, _delay) = aht20.destroy();
+        mock.done(); // verify expectations
+
+        assert!(measurement.temperature > 22.5 && measurement.temperature < 22.6);
+        assert!(measurement.humidity > 39.7 && measurement.humidity < 39.8);
+    }
+
+    /// Test that a measurement gives up once the retry budget is spent.
+    #[test]
+    fn measure_retry_budget_exhausted() {
+        let mut bad_frame = measurement_frame();
+        bad_frame[5] ^= 0b0000_0001; // Corrupt the data so the CRC won't match.
+        let mut expectations = vec![];
+        // One attempt, then one retry.
+        for _ in 0..2 {
+            expectations.push(trigger_measurement());
+            expectations.push(Transaction::read(SENSOR_ADDRESS, vec![0b0000_1000]));
+            expectations.push(Transaction::read(SENSOR_ADDRESS, bad_frame.clone()));
+        }
+        let mock_i2c = I2cMock::new(&expectations);
+        let mut mock_delay = MockDelay::new();
+
+        let aht20 = AHT20::builder(mock_i2c).retry_budget(1).build().unwrap();
+        let mut aht20: AHT20<_, Ready> = aht20.into_state();
+        assert_eq!(aht20.measure(&mut mock_delay), Err(Error::InvalidCrc));
+
+        aht20.destroy().done();
+    }
+
+    /// Test that the compensation offsets are applied to a measurement.
+    #[test]
+    fn measure_compensated() {
+        let expectations = vec![
+            trigger_measurement(),
+            Transaction::read(SENSOR_ADDRESS, vec![0b0000_1000]),
+            Transaction::read(SENSOR_ADDRESS, measurement_frame()),
+        ];
+        let mock_i2c = I2cMock::new(&expectations);
+        let mut mock_delay = MockDelay::new();
+
+        let aht20 = AHT20::builder(mock_i2c)
+            .compensation(super::Compensation {
+                temperature_offset: -2.5,
+                humidity_offset: 10.0,
+            })
+            .build()
+            .unwrap();
+        let mut aht20: AHT20<_, Ready> = aht20.into_state();
+        let measurement = aht20.measure(&mut mock_delay).unwrap();
+
+        aht20.destroy().done();
+
+        assert!(measurement.temperature > 20.0 && measurement.temperature < 20.1);
+        assert!(measurement.humidity > 49.7 && measurement.humidity < 49.8);
+    }
+
+    /// Test that Validation::CrcOnly doesn't check the repeated status byte.
+    #[test]
+    fn measure_once_crc_only_validation() {
+        let expectations = vec![
+            trigger_measurement(),
+            Transaction::read(SENSOR_ADDRESS, vec![0b0000_1000]),
+            // The frame from measure_once_ready_misreported, which reports busy.
+            Transaction::read(
+                SENSOR_ADDRESS,
+                vec![0x9c, 0x65, 0xb4, 0x25, 0xcd, 0x26, 0x2a],
+            ),
+        ];
+        let mock_i2c = I2cMock::new(&expectations);
+        let mut mock_delay = MockDelay::new();
+
+        let aht20 = AHT20::builder(mock_i2c)
+            .validation(super::Validation::CrcOnly)
+            .build()
+            .unwrap();
+        let mut aht20: AHT20<_, Ready> = aht20.into_state();
+        assert!(aht20.measure_once(&mut mock_delay).is_ok());
+
+        aht20.destroy().done();
+    }
+
+    /// Test that adaptive timing starts with a short wait, and falls back to the full measurement
+    /// delay after an UnexpectedBusy.
+    #[test]
+    fn measure_adaptive_timing() {
+        let expectations = vec![
+            // Learning: the short wait, then polled until ready.
+            trigger_measurement(),
+            Transaction::read(SENSOR_ADDRESS, vec![0b1001_1000]),
+            Transaction::read(SENSOR_ADDRESS, vec![0b0001_1000]),
+            Transaction::read(SENSOR_ADDRESS, measurement_frame()),
+            // The frame contradicts the ready status.
+            trigger_measurement(),
+            Transaction::read(SENSOR_ADDRESS, vec![0b0001_1000]),
+            Transaction::read(
+                SENSOR_ADDRESS,
+                vec![0x9c, 0x65, 0xb4, 0x25, 0xcd, 0x26, 0x2a],
+            ),
+            // The retry waits the full 80ms.
+            trigger_measurement(),
+            Transaction::read(SENSOR_ADDRESS, vec![0b0001_1000]),
+            Transaction::read(SENSOR_ADDRESS, measurement_frame()),
+        ];
+        let mock_i2c = I2cMock::new(&expectations);
+        let mut mock_delay = CheckedDelay::new(&[
+            DelayTransaction::delay_ms(30),
+            DelayTransaction::delay_ms(1),
+            DelayTransaction::delay_ms(30),
+            DelayTransaction::delay_ms(80),
+        ]);
+
+        let aht20 = AHT20::builder(mock_i2c)
+            .adaptive_timing(super::AdaptiveTiming::default())
+            .build()
+            .unwrap();
+        let mut aht20: AHT20<_, Ready> = aht20.into_state();
+        assert_eq!(aht20.measurement_wait_ms(), 30);
+        aht20.measure(&mut mock_delay).unwrap();
+        aht20.measure(&mut mock_delay).unwrap();
+        assert_eq!(aht20.measurement_wait_ms(), 80);
+
+        aht20.destroy().done();
+        mock_delay.done();
+    }
+
+    /// Test that polling with full frames gives the same reading as polling the status byte, with
+    /// one read fewer.
+    #[test]
+    fn measure_full_frame_matches_status_byte() {
+        let busy_frame = vec![0x9c, 0x65, 0xb4, 0x25, 0xcd, 0x26, 0x2a];
+
+        let status_byte_expectations = vec![
+            trigger_measurement(),
+            Transaction::read(SENSOR_ADDRESS, vec![0b1001_1000]),
+            Transaction::read(SENSOR_ADDRESS, vec![0b0001_1000]),
+            Transaction::read(SENSOR_ADDRESS, measurement_frame()),
+        ];
+        let full_frame_expectations = vec![
+            trigger_measurement(),
+            Transaction::read(SENSOR_ADDRESS, busy_frame),
+            Transaction::read(SENSOR_ADDRESS, measurement_frame()),
+        ];
+        let mut mock_delay = MockDelay::new();
+
+        let aht20 = AHT20::builder(I2cMock::new(&status_byte_expectations))
+            .poll_mode(super::PollMode::StatusByte)
+            .build()
+            .unwrap();
+        let mut aht20: AHT20<_, Ready> = aht20.into_state();
+        let status_byte_reading = aht20.measure(&mut mock_delay).unwrap();
+        aht20.destroy().done();
+
+        let aht20 = AHT20::builder(I2cMock::new(&full_frame_expectations))
+            .poll_mode(super::PollMode::FullFrame)
+            .build()
+            .unwrap();
+        let mut aht20: AHT20<_, Ready> = aht20.into_state();
+        let full_frame_reading = aht20.measure(&mut mock_delay).unwrap();
+        aht20.destroy().done();
+
+        assert_eq!(status_byte_reading, full_frame_reading);
+    }
+
+    /// Test that a ready frame with a bad CRC is read again when polling with full frames, and
+    /// rejected if it stays bad.
+    #[test]
+    fn measure_once_full_frame_bad_crc() {
+        let mut bad_frame = measurement_frame();
+        bad_frame[5] ^= 0b0000_0001;
+        let expectations = vec![
+            trigger_measurement(),
+            Transaction::read(SENSOR_ADDRESS, bad_frame.clone()),
+            Transaction::read(SENSOR_ADDRESS, measurement_frame()),
+            trigger_measurement(),
+            Transaction::read(SENSOR_ADDRESS, bad_frame.clone()),
+            Transaction::read(SENSOR_ADDRESS, bad_frame.clone()),
+            Transaction::read(SENSOR_ADDRESS, bad_frame),
+        ];
+        let mock_i2c = I2cMock::new(&expectations);
+        let mut mock_delay = MockDelay::new();
+
+        let aht20 = AHT20::builder(mock_i2c)
+            .poll_mode(super::PollMode::FullFrame)
+            .build()
+            .unwrap();
+        let mut aht20: AHT20<_, Ready> = aht20.into_state();
+        assert!(aht20.measure_once(&mut mock_delay).is_ok());
+        assert_eq!(aht20.measure_once(&mut mock_delay), Err(Error::InvalidCrc));
+
+        aht20.destroy().done();
+    }
+
     /// Test a valid CRC invocation.
     #[test]
     fn crc_correct() {
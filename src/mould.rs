@@ -0,0 +1,364 @@
+//! Mould growth risk, with the VTT model.
+//!
+//! The VTT model (Hukka and Viitanen 1999, extended to other materials by Ojanen et al. 2010)
+//! estimates how much mould has grown on a surface from its temperature and humidity history. It
+//! tracks a mould index from 0 to 6:
+//!
+//! | Index | Growth                                                           |
+//! |-------|------------------------------------------------------------------|
+//! | 0     | None.                                                            |
+//! | 1     | Some growth, visible only under a microscope.                    |
+//! | 2     | Moderate growth under a microscope, more than 10% coverage.      |
+//! | 3     | Some growth visible to the eye.                                  |
+//! | 4     | Visible growth, more than 10% coverage.                          |
+//! | 5     | Plenty of visible growth, more than 50% coverage.                |
+//! | 6     | Heavy growth, close to 100% coverage.                            |
+//!
+//! Mould grows while the humidity is above a critical level, which depends on the temperature
+//! and the material, and recedes slowly once it drops below it. `MouldGrowth` runs the model one
+//! timestamped reading at a time, so it can follow months of readings on a logger without storing
+//! them:
+//!
+//!     use aht20_driver::{MouldGrowth, MouldSettings, SensitivityClass, SensorReading};
+//!
+//!     let mut mould = MouldGrowth::new(MouldSettings {
+//!         sensitivity: SensitivityClass::Sensitive,
+//!         ..MouldSettings::default()
+//!     })
+//!     .unwrap();
+//!     // A damp attic, read hourly for two weeks.
+//!     let damp = SensorReading { humidity: 95.0, temperature: 20.0 };
+//!     for hour in 0..=14 * 24 {
+//!         mould.update(damp, hour * 3_600_000);
+//!     }
+//!     assert!(mould.index() > 0.5);
+//!
+//! The conditions of each reading are taken to hold until the next one, so readings should be
+//! close enough together to follow the changes, an hour apart or less.
+
+use crate::SensorReading;
+
+/// Milliseconds in an hour.
+const HOUR_MS: f32 = 3_600_000.0;
+/// The highest mould index.
+const MAX_INDEX: f32 = 6.0;
+
+/// How quickly mould recedes in unfavourable conditions, in index per hour, for the first 6
+/// hours, and from 24 hours on. It doesn't change in between.
+const EARLY_DECLINE_PER_HOUR: f32 = 0.00133;
+const LATE_DECLINE_PER_HOUR: f32 = 0.000667;
+/// The end of the early decline, and the start of the late decline, in hours.
+const EARLY_DECLINE_HOURS: f32 = 6.0;
+const LATE_DECLINE_HOURS: f32 = 24.0;
+
+/// How sensitive a material is to mould, from Ojanen et al.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "use-defmt", derive(defmt::Format))]
+pub enum SensitivityClass {
+    /// Untreated wood, and materials with a lot of nutrients for mould.
+    VerySensitive,
+    /// Planed wood, paper-coated products and wood-based boards.
+    Sensitive,
+    /// Cement and plastic based materials, mineral wool.
+    MediumResistant,
+    /// Glass and metal, and materials with strong fungicides.
+    Resistant,
+}
+
+/// The model's parameters for one sensitivity class.
+struct ClassParameters {
+    /// The growth rate multiplier, before and after the index reaches 1.
+    k1: (f32, f32),
+    /// The coefficients for the largest index the humidity can support.
+    a: f32,
+    b: f32,
+    c: f32,
+    /// The lowest critical humidity, in percent.
+    rh_min: f32,
+}
+
+impl SensitivityClass {
+    fn parameters(self) -> ClassParameters {
+        match self {
+            SensitivityClass::VerySensitive => ClassParameters {
+                k1: (1.0, 2.0),
+                a: 1.0,
+                b: 7.0,
+                c: 2.0,
+                rh_min: 80.0,
+            },
+            SensitivityClass::Sensitive => ClassParameters {
+                k1: (0.578, 0.386),
+                a: 0.3,
+                b: 6.0,
+                c: 1.0,
+                rh_min: 80.0,
+            },
+            SensitivityClass::MediumResistant => ClassParameters {
+                k1: (0.072, 0.097),
+                a: 0.0,
+                b: 5.0,
+                c: 1.5,
+                rh_min: 85.0,
+            },
+            SensitivityClass::Resistant => ClassParameters {
+                k1: (0.033, 0.014),
+                a: 0.0,
+                b: 3.0,
+                c: 1.0,
+                rh_min: 85.0,
+            },
+        }
+    }
+}
+
+/// Settings for `MouldGrowth`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "use-defmt", derive(defmt::Format))]
+pub struct MouldSettings {
+    /// How sensitive the material is.
+    pub sensitivity: SensitivityClass,
+    /// How quickly mould recedes on the material in unfavourable conditions, relative to pine
+    /// sapwood. Ojanen et al. give 1 for pine, 0.5 for most other materials, and as low as 0.1
+    /// for some.
+    pub decline_coefficient: f32,
+}
+
+impl Default for MouldSettings {
+    fn default() -> Self {
+        MouldSettings {
+            sensitivity: SensitivityClass::VerySensitive,
+            decline_coefficient: 1.0,
+        }
+    }
+}
+
+/// The error from `MouldGrowth::new`, for a decline coefficient that is negative or not finite.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "use-defmt", derive(defmt::Format))]
+pub struct InvalidMouldSettings;
+
+impl core::fmt::Display for InvalidMouldSettings {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> Result<(), core::fmt::Error> {
+        write!(f, "invalid mould model settings")
+    }
+}
+
+impl core::error::Error for InvalidMouldSettings {}
+
+/// Runs the VTT mould growth model over timestamped readings.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MouldGrowth {
+    settings: MouldSettings,
+    index: f32,
+    /// The previous reading, and when it was taken.
+    last: Option<(SensorReading, u64)>,
+    /// When the current unfavourable period started, if conditions are unfavourable.
+    unfavourable_since_ms: Option<u64>,
+}
+
+impl MouldGrowth {
+    /// Start the model with a mould index of 0. Returns `InvalidMouldSettings` if the decline
+    /// coefficient is negative or not finite.
+    pub fn new(settings: MouldSettings) -> Result<Self, InvalidMouldSettings> {
+        if !settings.decline_coefficient.is_finite() || settings.decline_coefficient < 0.0 {
+            return Err(InvalidMouldSettings);
+        }
+        Ok(MouldGrowth {
+            settings,
+            index: 0.0,
+            last: None,
+            unfavourable_since_ms: None,
+        })
+    }
+
+    /// Add a reading taken at `timestamp_ms`, and return the updated mould index.
+    ///
+    /// The previous reading's conditions are applied up to `timestamp_ms`. A reading older than
+    /// the previous one is ignored, and one taken at the same time replaces it.
+    pub fn update(&mut self, reading: SensorReading, timestamp_ms: u64) -> f32 {
+        match self.last {
+            Some((_, last_ms)) if timestamp_ms < last_ms => return self.index,
+            Some((last_reading, last_ms)) if timestamp_ms > last_ms => {
+                self.advance(last_reading, last_ms, timestamp_ms)
+            }
+            _ => {}
+        }
+        self.last = Some((reading, timestamp_ms));
+        self.index
+    }
+
+    /// The mould index, from 0 to 6.
+    pub fn index(&self) -> f32 {
+        self.index
+    }
+
+    /// Start over from a mould index of 0, for example after the surface has been cleaned.
+    pub fn reset(&mut self) {
+        self.index = 0.0;
+        self.last = None;
+        self.unfavourable_since_ms = None;
+    }
+
+    /// Apply the conditions of `reading` from `from_ms` to `to_ms`.
+    fn advance(&mut self, reading: SensorReading, from_ms: u64, to_ms: u64) {
+        let parameters = self.settings.sensitivity.parameters();
+        let (temperature, humidity) = (reading.temperature, reading.humidity.clamp(0.0, 100.0));
+        let rh_crit = critical_humidity(temperature, parameters.rh_min);
+
+        if temperature > 0.0 && humidity >= rh_crit && rh_crit < 100.0 {
+            self.unfavourable_since_ms = None;
+
+            // The largest index these conditions can support, which growth slows towards.
+            let excess = (rh_crit - humidity) / (rh_crit - 100.0);
+            let max_index = parameters.a + parameters.b * excess - parameters.c * excess * excess;
+            let k1 = if self.index < 1.0 {
+                parameters.k1.0
+            } else {
+                parameters.k1.1
+            };
+            let k2 = (1.0 - libm::expf(2.3 * (self.index - max_index))).max(0.0);
+
+            // The number of weeks to reach an index of 1 on pine sapwood.
+            let weeks =
+                libm::expf(-0.68 * libm::logf(temperature) - 13.9 * libm::logf(humidity) + 66.02);
+            let days = (to_ms - from_ms) as f32 / (24.0 * HOUR_MS);
+            self.index += days / (7.0 * weeks) * k1 * k2;
+        } else {
+            let since_ms = *self.unfavourable_since_ms.get_or_insert(from_ms);
+            let start_hours = (from_ms - since_ms) as f32 / HOUR_MS;
+            let end_hours = (to_ms - since_ms) as f32 / HOUR_MS;
+            let overlap = |from: f32, to: f32| (end_hours.min(to) - start_hours.max(from)).max(0.0);
+
+            let decline = EARLY_DECLINE_PER_HOUR * overlap(0.0, EARLY_DECLINE_HOURS)
+                + LATE_DECLINE_PER_HOUR * overlap(LATE_DECLINE_HOURS, f32::INFINITY);
+            self.index -= decline * self.settings.decline_coefficient;
+        }
+        self.index = self.index.clamp(0.0, MAX_INDEX);
+    }
+}
+
+/// The humidity above which mould grows, in percent. It rises in the cold, and is never below
+/// `rh_min`.
+fn critical_humidity(temperature: f32, rh_min: f32) -> f32 {
+    if temperature <= 20.0 {
+        let t = temperature;
+        (-0.00267 * t * t * t + 0.160 * t * t - 3.13 * t + 100.0).max(rh_min)
+    } else {
+        rh_min
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{InvalidMouldSettings, MouldGrowth, MouldSettings, SensitivityClass};
+    use crate::test_support::reading;
+    use crate::SensorReading;
+
+    const HOUR_MS: u64 = 3_600_000;
+
+    fn model(sensitivity: SensitivityClass) -> MouldGrowth {
+        MouldGrowth::new(MouldSettings {
+            sensitivity,
+            decline_coefficient: 1.0,
+        })
+        .unwrap()
+    }
+
+    /// Feed hourly readings of the same conditions from `start_hour` for `hours`.
+    fn run(mould: &mut MouldGrowth, conditions: SensorReading, start_hour: u64, hours: u64) {
+        for hour in start_hour..=start_hour + hours {
+            mould.update(conditions, hour * HOUR_MS);
+        }
+    }
+
+    /// Mould grows in damp conditions, fastest on the most sensitive materials, and levels off at
+    /// what the humidity can support.
+    #[test]
+    fn growth_by_sensitivity() {
+        let damp = reading(97.0, 22.0);
+        let classes = [
+            SensitivityClass::VerySensitive,
+            SensitivityClass::Sensitive,
+            SensitivityClass::MediumResistant,
+            SensitivityClass::Resistant,
+        ];
+        let indexes = classes.map(|class| {
+            let mut mould = model(class);
+            run(&mut mould, damp, 0, 8 * 7 * 24);
+            mould.index()
+        });
+        assert!(
+            indexes.windows(2).all(|pair| pair[0] > pair[1]),
+            "{indexes:?}"
+        );
+        assert!(indexes[0] > 3.0, "{indexes:?}");
+        assert!(indexes[3] > 0.0, "{indexes:?}");
+
+        // On a very sensitive material at 22C and 97%, the index grows by 1 / (7 * 1.39 weeks) a
+        // day until it reaches 1.
+        let mut mould = model(SensitivityClass::VerySensitive);
+        run(&mut mould, damp, 0, 7 * 24);
+        assert!(
+            (mould.index() - 7.0 / (7.0 * 1.391)).abs() < 0.01,
+            "{}",
+            mould.index()
+        );
+
+        // It can't go past 6, and slows down long before.
+        run(&mut mould, damp, 7 * 24, 52 * 7 * 24);
+        assert!(mould.index() <= 6.0);
+        assert!(mould.index() > 5.0);
+    }
+
+    /// Nothing grows below the critical humidity, or in the cold.
+    #[test]
+    fn no_growth_in_unfavourable_conditions() {
+        let mut mould = model(SensitivityClass::VerySensitive);
+        run(&mut mould, reading(75.0, 25.0), 0, 30 * 24);
+        assert_eq!(mould.index(), 0.0);
+        // 85% is enough at 20C, but not at 5C.
+        run(&mut mould, reading(85.0, 5.0), 30 * 24, 30 * 24);
+        assert_eq!(mould.index(), 0.0);
+        run(&mut mould, reading(95.0, -5.0), 60 * 24, 30 * 24);
+        assert_eq!(mould.index(), 0.0);
+    }
+
+    /// Mould recedes quickly for 6 hours of dry conditions, then not at all until a day has
+    /// passed, then slowly.
+    #[test]
+    fn decline() {
+        let mut mould = model(SensitivityClass::VerySensitive);
+        run(&mut mould, reading(97.0, 22.0), 0, 21 * 24);
+        let grown = mould.index();
+        let dry = reading(50.0, 22.0);
+
+        let start_hour = 21 * 24;
+        run(&mut mould, dry, start_hour, 6);
+        assert!((grown - mould.index() - 6.0 * 0.00133).abs() < 1e-4);
+        let after_six_hours = mould.index();
+        run(&mut mould, dry, start_hour + 6, 18);
+        assert_eq!(mould.index(), after_six_hours);
+        run(&mut mould, dry, start_hour + 24, 100);
+        assert!((after_six_hours - mould.index() - 100.0 * 0.000667).abs() < 1e-4);
+
+        let mut halved = model(SensitivityClass::VerySensitive);
+        halved.settings.decline_coefficient = 0.5;
+        run(&mut halved, reading(97.0, 22.0), 0, 21 * 24);
+        run(&mut halved, dry, start_hour, 6);
+        assert!((grown - halved.index() - 3.0 * 0.00133).abs() < 1e-4);
+
+        mould.reset();
+        assert_eq!(mould.index(), 0.0);
+    }
+
+    /// A negative decline coefficient is rejected.
+    #[test]
+    fn rejects_invalid_settings() {
+        let settings = MouldSettings {
+            decline_coefficient: -0.5,
+            ..MouldSettings::default()
+        };
+        assert_eq!(MouldGrowth::new(settings), Err(InvalidMouldSettings));
+    }
+}
@@ -0,0 +1,198 @@
+//! Oversampling, to smooth out the jitter between single readings.
+//!
+//! Back-to-back AHT20 readings jitter by around 0.05°C and 0.1%RH. `measure_oversampled` takes
+//! several readings and combines them into one. The readings are combined as the sensor's raw
+//! 20-bit values, before they are converted to degrees and percent, and the spread between the
+//! lowest and highest reading is returned as an indication of how steady they were.
+//!
+//!     # use embedded_hal_mock::eh1::delay::NoopDelay as MockDelay;
+//!     # use embedded_hal_mock::eh1::i2c::Mock as I2cMock;
+//!     # use embedded_hal_mock::eh1::i2c::Transaction;
+//!     use aht20_driver::{Aggregation, AHT20, SENSOR_ADDRESS};
+//!
+//!     # let mut expectations = vec![Transaction::read(SENSOR_ADDRESS, vec![0b0001_1000])];
+//!     # for _ in 0..5 {
+//!     #     expectations.push(Transaction::write(SENSOR_ADDRESS, vec![0xAC, 0x33, 0x00]));
+//!     #     expectations.push(Transaction::read(SENSOR_ADDRESS, vec![0b0001_1000]));
+//!     #     expectations.push(Transaction::read(
+//!     #         SENSOR_ADDRESS,
+//!     #         vec![0x1c, 0x65, 0xb4, 0x25, 0xcd, 0x26, 0xc6],
+//!     #     ));
+//!     # }
+//!     # let mock_i2c = I2cMock::new(&expectations);
+//!     # let mut delay = MockDelay::new();
+//!     let mut aht20 = AHT20::new(mock_i2c, SENSOR_ADDRESS).init(&mut delay).unwrap();
+//!     // Five readings 100ms apart, dropping the highest and lowest.
+//!     let oversampled = aht20
+//!         .measure_oversampled::<5>(&mut delay, 100, Aggregation::TrimmedMean { trim: 1 })
+//!         .unwrap();
+//!     assert_eq!(oversampled.temperature_spread, 0.0);
+//!     # aht20.destroy().done();
+//!
+//! Keep the datasheet's advice to measure at most once every 2 seconds in mind. Oversampling with
+//! little spacing between readings will warm the sensor up slightly.
+
+use embedded_hal::delay::DelayNs;
+use embedded_hal::i2c::I2c;
+
+use crate::{Error, Ready, SensorReading, AHT20};
+
+/// How `measure_oversampled` combines its readings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "use-defmt", derive(defmt::Format))]
+pub enum Aggregation {
+    /// The average of all readings.
+    Mean,
+    /// The middle reading, or the average of the middle two for an even number of readings.
+    /// This ignores the odd outlier.
+    Median,
+    /// The average after dropping the `trim` highest and `trim` lowest readings. At least one
+    /// reading is always kept, so a large `trim` works out as the median.
+    TrimmedMean { trim: usize },
+}
+
+/// The combined result of `measure_oversampled`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OversampledReading {
+    /// The combined reading.
+    pub reading: SensorReading,
+    /// The difference between the highest and lowest temperature read, in degrees Celsius.
+    pub temperature_spread: f32,
+    /// The difference between the highest and lowest relative humidity read, in percent.
+    pub humidity_spread: f32,
+}
+
+impl<I> AHT20<I, Ready>
+where
+    I: I2c,
+{
+    /// Take `N` readings `spacing_ms` apart, and combine them with `method`.
+    ///
+    /// This takes at least `N` times the measurement time, plus the spacing between readings. The
+    /// readings are kept on the stack, nothing is allocated. Compensation offsets configured with
+    /// the builder are applied to the combined reading.
+    pub fn measure_oversampled<const N: usize>(
+        &mut self,
+        delay: &mut impl DelayNs,
+        spacing_ms: u32,
+        method: Aggregation,
+    ) -> Result<OversampledReading, Error<I::Error>> {
+        const { assert!(N > 0, "measure_oversampled needs at least one reading") };
+
+        let mut humidity = [0u32; N];
+        let mut temperature = [0u32; N];
+        for i in 0..N {
+            if i > 0 {
+                delay.delay_ms(spacing_ms);
+            }
+            let sensor_data = self.measure_retrying(delay)?;
+            (humidity[i], temperature[i]) = SensorReading::raw_from_bytes(sensor_data);
+        }
+
+        humidity.sort_unstable();
+        temperature.sort_unstable();
+        let reading = SensorReading::from_raw(
+            aggregate(&humidity, method),
+            aggregate(&temperature, method),
+        )
+        .compensated(self.config.compensation);
+
+        // The full 20-bit range spans 100% humidity, or 200°C.
+        let full_scale = (1 << 20) as f32;
+        Ok(OversampledReading {
+            reading,
+            temperature_spread: (temperature[N - 1] - temperature[0]) as f32 / full_scale * 200.0,
+            humidity_spread: (humidity[N - 1] - humidity[0]) as f32 / full_scale * 100.0,
+        })
+    }
+}
+
+/// Combine the sorted, non-empty raw values with `method`.
+fn aggregate(sorted: &[u32], method: Aggregation) -> u32 {
+    let len = sorted.len();
+    let kept = match method {
+        Aggregation::Mean => sorted,
+        Aggregation::Median => {
+            // One value for an odd length, the middle two for an even one.
+            &sorted[(len - 1) / 2..len / 2 + 1]
+        }
+        Aggregation::TrimmedMean { trim } => {
+            let trim = trim.min((len - 1) / 2);
+            &sorted[trim..len - trim]
+        }
+    };
+    mean(kept)
+}
+
+/// The mean of the non-empty raw values, rounded to the nearest integer.
+fn mean(values: &[u32]) -> u32 {
+    let count = values.len() as u64;
+    let sum: u64 = values.iter().map(|value| u64::from(*value)).sum();
+    ((sum + count / 2) / count) as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{aggregate, Aggregation};
+    use crate::test_support::{measurement, raw_frame};
+    use crate::{Ready, AHT20, SENSOR_ADDRESS};
+    use embedded_hal_mock::eh1::delay::{CheckedDelay, Transaction as DelayTransaction};
+    use embedded_hal_mock::eh1::i2c::Mock as I2cMock;
+
+    /// Each method on sorted raw values.
+    #[test]
+    fn aggregate_methods() {
+        let sorted = [10, 20, 30, 40, 100];
+        assert_eq!(aggregate(&sorted, Aggregation::Mean), 40);
+        assert_eq!(aggregate(&sorted, Aggregation::Median), 30);
+        assert_eq!(aggregate(&[10, 20, 30, 100], Aggregation::Median), 25);
+        assert_eq!(aggregate(&sorted, Aggregation::TrimmedMean { trim: 1 }), 30);
+        assert_eq!(aggregate(&sorted, Aggregation::TrimmedMean { trim: 0 }), 40);
+        // Trimming too much leaves the median.
+        assert_eq!(aggregate(&sorted, Aggregation::TrimmedMean { trim: 9 }), 30);
+        assert_eq!(aggregate(&[7], Aggregation::TrimmedMean { trim: 9 }), 7);
+    }
+
+    /// Readings are spaced out, combined, and their spread reported.
+    #[test]
+    fn measure_oversampled_median() {
+        // Half scale humidity is 50%, and temperature is 50C.
+        let half = 1 << 19;
+        let mut expectations = measurement(raw_frame(half, half));
+        expectations.extend(measurement(raw_frame(half + 1000, half - 1000)));
+        expectations.extend(measurement(raw_frame(half + 20_000, half + 2000)));
+        let mock_i2c = I2cMock::new(&expectations);
+        let mut mock_delay = CheckedDelay::new(&[
+            DelayTransaction::delay_ms(80),
+            DelayTransaction::delay_ms(100),
+            DelayTransaction::delay_ms(80),
+            DelayTransaction::delay_ms(100),
+            DelayTransaction::delay_ms(80),
+        ]);
+
+        let mut aht20: AHT20<_, Ready> = AHT20::new(mock_i2c, SENSOR_ADDRESS).into_state();
+        let oversampled = aht20
+            .measure_oversampled::<3>(&mut mock_delay, 100, Aggregation::Median)
+            .unwrap();
+
+        aht20.destroy().done();
+        mock_delay.done();
+
+        let raw_to_percent = 100.0 / (1 << 20) as f32;
+        let raw_to_celsius = 200.0 / (1 << 20) as f32;
+        let close = |a: f32, b: f32| (a - b).abs() < 1e-4;
+        assert!(close(
+            oversampled.reading.humidity,
+            (half + 1000) as f32 * raw_to_percent
+        ));
+        assert!(close(oversampled.reading.temperature, 50.0));
+        assert!(close(
+            oversampled.humidity_spread,
+            20_000.0 * raw_to_percent
+        ));
+        assert!(close(
+            oversampled.temperature_spread,
+            3000.0 * raw_to_celsius
+        ));
+    }
+}
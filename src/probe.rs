@@ -0,0 +1,243 @@
+//! Sensor probing and variant detection.
+//!
+//! `AHT20::probe` answers "is there an AHT-family sensor at this address, and which one?" without
+//! running `init`. It does this in two steps:
+//!
+//! 1. Read a status byte. A NACK on the address means nothing is there. A NACK on a data byte
+//!    comes from a device that answered, so it is returned as an error like any other bus error.
+//! 2. Trigger a measurement, wait for the status byte to report ready, and read a 7 byte frame.
+//!
+//! The frame is then classified:
+//!
+//! * The AHT20 and its relatives end their frame in a CRC byte. A frame with a bad CRC is read
+//!   once more before giving up on it, so a glitch on the bus doesn't hide an AHT20.
+//! * Every AHT sensor repeats its status byte at the start of the frame. A frame that doesn't
+//!   start with a ready status isn't from an AHT sensor.
+//! * The older AHT10 only sends 6 bytes, so the 7th byte reads as `0xFF` from the idle, pulled-up
+//!   bus. The AHT10 can also be left in cycle mode, which it reports in the status byte and the
+//!   AHT20 family doesn't use, so a sensor in cycle mode without a CRC is taken as an AHT10 too.
+//!
+//! The AHT20, DHT20, AHT21, AHT25, AM2301B and AHT30 can't be told apart from their responses, so
+//! they are all reported as `DetectedVariant::Aht20Family`.
+
+use embedded_hal::delay::DelayNs;
+use embedded_hal::i2c::{Error as _, ErrorKind, I2c, NoAcknowledgeSource};
+
+use crate::{compute_crc, Error, Mode, SensorStatus, Uninit, AHT20};
+
+/// How many times the status is polled waiting for the probe measurement before giving up. At 1ms
+/// per poll this is far beyond what a working sensor needs.
+const PROBE_MAX_POLLS: u32 = 100;
+
+/// The kind of sensor found by `AHT20::probe`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "use-defmt", derive(defmt::Format))]
+pub enum DetectedVariant {
+    /// An AHT10. Its measurement frame has no CRC byte.
+    Aht10,
+    /// An AHT20 or one of the parts sharing its protocol. The measurement frame ends in a valid
+    /// CRC byte.
+    Aht20Family,
+    /// Something acknowledged the address, but didn't respond the way an AHT sensor does.
+    Unknown,
+}
+
+impl<I> AHT20<I, Uninit>
+where
+    I: I2c,
+{
+    /// Check whether there is an AHT sensor at this driver's address, and guess which one.
+    ///
+    /// Returns `Ok(None)` if the address isn't acknowledged. Other bus errors, including a NACK on
+    /// a data byte, are returned as `Error::I2c`. This triggers one measurement, so it takes at least the variant's
+    /// measurement delay, 80ms for the AHT20. The sensor doesn't need to be initialized first,
+    /// and isn't initialized by this.
+    pub fn probe(
+        &mut self,
+        delay: &mut impl DelayNs,
+    ) -> Result<Option<DetectedVariant>, Error<I::Error>> {
+        match self.check_status() {
+            Ok(_) => {}
+            Err(Error::I2c(e))
+                if matches!(
+                    e.kind(),
+                    ErrorKind::NoAcknowledge(
+                        NoAcknowledgeSource::Address | NoAcknowledgeSource::Unknown
+                    )
+                ) =>
+            {
+                #[cfg(feature = "use-defmt")]
+                defmt::debug!("probe: address not acknowledged.");
+                return Ok(None);
+            }
+            Err(other) => return Err(other),
+        }
+
+        self.send_trigger_measurement()?;
+        delay.delay_ms(self.config.variant.measurement_delay_ms);
+
+        let mut polls = 0;
+        let status = loop {
+            let status = self.check_status()?;
+            if status.is_ready() {
+                break status;
+            }
+            polls += 1;
+            if polls > PROBE_MAX_POLLS {
+                #[cfg(feature = "use-defmt")]
+                defmt::debug!("probe: sensor never reported ready.");
+                return Ok(Some(DetectedVariant::Unknown));
+            }
+            delay.delay_ms(self.config.variant.poll_interval_ms);
+        };
+
+        let mut read_buffer = [0u8; 7];
+        self.i2c
+            .read(self.config.address, &mut read_buffer)
+            .map_err(Error::I2c)?;
+        if compute_crc(&read_buffer[..6]) != read_buffer[6] {
+            #[cfg(feature = "use-defmt")]
+            defmt::debug!("probe: CRC mismatch, reading the frame again.");
+            self.i2c
+                .read(self.config.address, &mut read_buffer)
+                .map_err(Error::I2c)?;
+        }
+
+        let detected = classify(status, &read_buffer);
+        #[cfg(feature = "use-defmt")]
+        defmt::debug!("probe: detected {}.", detected);
+        Ok(Some(detected))
+    }
+}
+
+/// Guess the variant from the ready status byte and the measurement frame that followed it.
+fn classify(status: SensorStatus, frame: &[u8; 7]) -> DetectedVariant {
+    if compute_crc(&frame[..6]) == frame[6] {
+        DetectedVariant::Aht20Family
+    } else if !SensorStatus::new(frame[0]).is_ready() {
+        DetectedVariant::Unknown
+    } else if frame[6] == 0xFF || status.mode() == Mode::Cycle {
+        DetectedVariant::Aht10
+    } else {
+        DetectedVariant::Unknown
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DetectedVariant;
+    use crate::test_support::{measurement_frame, trigger_measurement};
+    use crate::{Error, AHT20, SENSOR_ADDRESS};
+    use embedded_hal::i2c::{ErrorKind, NoAcknowledgeSource};
+    use embedded_hal_mock::eh1::delay::NoopDelay as MockDelay;
+    use embedded_hal_mock::eh1::i2c::Mock as I2cMock;
+    use embedded_hal_mock::eh1::i2c::Transaction;
+
+    /// The expectations for a probe whose sensor reports `status` when ready, and then sends
+    /// `frames`.
+    fn frame_expectations(status: u8, frames: &[Vec<u8>]) -> Vec<Transaction> {
+        let mut expectations = vec![
+            Transaction::read(SENSOR_ADDRESS, vec![status]),
+            trigger_measurement(),
+            Transaction::read(SENSOR_ADDRESS, vec![status]),
+        ];
+        expectations.extend(
+            frames
+                .iter()
+                .map(|frame| Transaction::read(SENSOR_ADDRESS, frame.clone())),
+        );
+        expectations
+    }
+
+    /// Probe with the given expectations, and return what was detected.
+    fn probe(expectations: &[Transaction]) -> Option<DetectedVariant> {
+        let mock_i2c = I2cMock::new(expectations);
+        let mut mock_delay = MockDelay::new();
+
+        let mut aht20 = AHT20::new(mock_i2c, SENSOR_ADDRESS);
+        let detected = aht20.probe(&mut mock_delay).unwrap();
+        aht20.destroy().done();
+        detected
+    }
+
+    /// Nothing answers at the address.
+    #[test]
+    fn probe_absent() {
+        let expectations = vec![Transaction::read(SENSOR_ADDRESS, vec![0])
+            .with_error(ErrorKind::NoAcknowledge(NoAcknowledgeSource::Address))];
+        let mock_i2c = I2cMock::new(&expectations);
+        let mut mock_delay = MockDelay::new();
+
+        let mut aht20 = AHT20::new(mock_i2c, SENSOR_ADDRESS);
+        assert_eq!(aht20.probe(&mut mock_delay), Ok(None));
+
+        aht20.destroy().done();
+    }
+
+    /// Bus errors other than a NACK are passed on.
+    #[test]
+    fn probe_bus_error() {
+        let expectations =
+            vec![Transaction::read(SENSOR_ADDRESS, vec![0]).with_error(ErrorKind::ArbitrationLoss)];
+        let mock_i2c = I2cMock::new(&expectations);
+        let mut mock_delay = MockDelay::new();
+
+        let mut aht20 = AHT20::new(mock_i2c, SENSOR_ADDRESS);
+        assert!(aht20.probe(&mut mock_delay).is_err());
+
+        aht20.destroy().done();
+    }
+
+    /// A NACK on a data byte comes from a device that is there, so it is an error, not absence.
+    #[test]
+    fn probe_data_nack() {
+        let data_nack = ErrorKind::NoAcknowledge(NoAcknowledgeSource::Data);
+        let expectations = vec![Transaction::read(SENSOR_ADDRESS, vec![0]).with_error(data_nack)];
+        let mock_i2c = I2cMock::new(&expectations);
+        let mut mock_delay = MockDelay::new();
+
+        let mut aht20 = AHT20::new(mock_i2c, SENSOR_ADDRESS);
+        assert_eq!(aht20.probe(&mut mock_delay), Err(Error::I2c(data_nack)));
+
+        aht20.destroy().done();
+    }
+
+    /// A frame ending in a valid CRC is from the AHT20 family, also after one bad read.
+    #[test]
+    fn probe_aht20_family() {
+        let frame = measurement_frame();
+        let expectations = frame_expectations(0b0001_1000, core::slice::from_ref(&frame));
+        assert_eq!(probe(&expectations), Some(DetectedVariant::Aht20Family));
+
+        let mut glitched = frame.clone();
+        glitched[3] ^= 0b0000_0100;
+        let expectations = frame_expectations(0b0001_1000, &[glitched, frame]);
+        assert_eq!(probe(&expectations), Some(DetectedVariant::Aht20Family));
+    }
+
+    /// A 6 byte frame followed by an idle bus, or cycle mode without a CRC, is from an AHT10.
+    #[test]
+    fn probe_aht10() {
+        let frame = vec![0x1c, 0x65, 0xb4, 0x25, 0xcd, 0x26, 0xff];
+        let expectations = frame_expectations(0b0001_1000, &[frame.clone(), frame]);
+        assert_eq!(probe(&expectations), Some(DetectedVariant::Aht10));
+
+        // Cycle mode, on a bus that doesn't read the missing byte as 0xFF.
+        let frame = vec![0x38, 0x65, 0xb4, 0x25, 0xcd, 0x26, 0x00];
+        let expectations = frame_expectations(0b0011_1000, &[frame.clone(), frame]);
+        assert_eq!(probe(&expectations), Some(DetectedVariant::Aht10));
+    }
+
+    /// A frame with a wrong CRC that isn't an idle bus either, or that doesn't repeat a ready
+    /// status.
+    #[test]
+    fn probe_unknown() {
+        let frame = vec![0x1c, 0x65, 0xb4, 0x25, 0xcd, 0x26, 0x00];
+        let expectations = frame_expectations(0b0001_1000, &[frame.clone(), frame]);
+        assert_eq!(probe(&expectations), Some(DetectedVariant::Unknown));
+
+        let frame = vec![0x9c, 0x65, 0xb4, 0x25, 0xcd, 0x26, 0xff];
+        let expectations = frame_expectations(0b0001_1000, &[frame.clone(), frame]);
+        assert_eq!(probe(&expectations), Some(DetectedVariant::Unknown));
+    }
+}
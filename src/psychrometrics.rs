@@ -0,0 +1,146 @@
+//! Quantities derived from temperature and relative humidity.
+//!
+//! These use the Magnus formula with the Sonntag (1990) coefficients, which is good to within
+//! about 0.35°C between -45°C and 60°C.
+
+use crate::SensorReading;
+
+/// Magnus coefficient, dimensionless.
+const MAGNUS_A: f32 = 17.62;
+/// Magnus coefficient, in degrees Celsius.
+const MAGNUS_B: f32 = 243.12;
+
+/// Saturation vapour pressure at 0°C, in hectopascals.
+const MAGNUS_C: f32 = 6.112;
+/// The specific gas constant of water vapour, 461.5 J/(kg·K), scaled for a pressure in hPa and
+/// a result in grams.
+const WATER_VAPOUR_CONSTANT: f32 = 0.004_615;
+/// 0°C in Kelvin.
+const ZERO_CELSIUS_K: f32 = 273.15;
+/// Standard atmospheric pressure at sea level, in hectopascals.
+const STANDARD_PRESSURE_HPA: f32 = 1013.25;
+/// The ratio of the molar masses of water and dry air, times 1000 for grams per kilogram.
+const MOLAR_MASS_RATIO_G_PER_KG: f32 = 621.97;
+
+/// The lowest relative humidity used, to keep the logarithm finite.
+const MIN_HUMIDITY: f32 = 0.01;
+
+/// The dew point in degrees Celsius, for a temperature in degrees Celsius and a relative humidity
+/// in percent.
+///
+/// Humidity is clamped to 0.01-100%.
+pub fn dew_point(temperature: f32, humidity: f32) -> f32 {
+    let humidity = humidity.clamp(MIN_HUMIDITY, 100.0);
+    let gamma = libm::logf(humidity / 100.0) + MAGNUS_A * temperature / (MAGNUS_B + temperature);
+    MAGNUS_B * gamma / (MAGNUS_A - gamma)
+}
+
+/// The absolute humidity in grams of water per cubic metre of air, for a temperature in degrees
+/// Celsius and a relative humidity in percent.
+///
+/// Unlike relative humidity, this can be compared between air at different temperatures. Humidity
+/// is clamped to 0-100%.
+pub fn absolute_humidity(temperature: f32, humidity: f32) -> f32 {
+    vapour_pressure_hpa(temperature, humidity)
+        / (WATER_VAPOUR_CONSTANT * (temperature + ZERO_CELSIUS_K))
+}
+
+/// The humidity ratio in grams of water per kilogram of dry air, for a temperature in degrees
+/// Celsius and a relative humidity in percent, at sea level pressure.
+///
+/// This is the vertical axis of a psychrometric chart. Humidity is clamped to 0-100%.
+pub fn humidity_ratio(temperature: f32, humidity: f32) -> f32 {
+    let vapour_hpa = vapour_pressure_hpa(temperature, humidity);
+    MOLAR_MASS_RATIO_G_PER_KG * vapour_hpa / (STANDARD_PRESSURE_HPA - vapour_hpa)
+}
+
+/// The partial pressure of water vapour, in hectopascals.
+fn vapour_pressure_hpa(temperature: f32, humidity: f32) -> f32 {
+    let saturation_hpa = MAGNUS_C * libm::expf(MAGNUS_A * temperature / (MAGNUS_B + temperature));
+    saturation_hpa * humidity.clamp(0.0, 100.0) / 100.0
+}
+
+/// An integer approximation of the dew point, for a temperature in degrees Celsius and a relative
+/// humidity in percent, for use without floating point math.
+///
+/// This is `temperature - (100 - humidity) / 5`, which is within about 2.5°C of `dew_point`
+/// between 0°C and 30°C at 50% humidity and above, including the rounding to whole degrees. Below
+/// 50% it gives a dew point that is too high, by more the drier the air: at 20°C, about 2°C too
+/// high at 40% and 8°C too high at 20%. Humidity is clamped to 0-100%.
+pub fn dew_point_no_fp(temperature: i32, humidity: i32) -> i32 {
+    temperature - (100 - humidity.clamp(0, 100)) / 5
+}
+
+impl SensorReading {
+    /// The dew point of this reading, in degrees Celsius. See `dew_point`.
+    pub fn dew_point(&self) -> f32 {
+        dew_point(self.temperature, self.humidity)
+    }
+
+    /// The absolute humidity of this reading, in grams per cubic metre. See `absolute_humidity`.
+    pub fn absolute_humidity(&self) -> f32 {
+        absolute_humidity(self.temperature, self.humidity)
+    }
+
+    /// The humidity ratio of this reading, in grams per kilogram of dry air. See
+    /// `humidity_ratio`.
+    pub fn humidity_ratio(&self) -> f32 {
+        humidity_ratio(self.temperature, self.humidity)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{absolute_humidity, dew_point, dew_point_no_fp, humidity_ratio};
+
+    /// Dew points match published tables to within 0.1C.
+    #[test]
+    fn dew_points() {
+        let close = |a: f32, b: f32| (a - b).abs() < 0.1;
+        assert!(close(dew_point(20.0, 100.0), 20.0));
+        assert!(close(dew_point(20.0, 50.0), 9.3));
+        assert!(close(dew_point(25.0, 60.0), 16.7));
+        assert!(close(dew_point(0.0, 80.0), -3.0));
+        assert!(dew_point(20.0, 0.0).is_finite());
+    }
+
+    /// Absolute humidities match published tables to within 0.1 g/m³.
+    #[test]
+    fn absolute_humidities() {
+        let close = |a: f32, b: f32| (a - b).abs() < 0.1;
+        assert!(close(absolute_humidity(20.0, 100.0), 17.3));
+        assert!(close(absolute_humidity(20.0, 50.0), 8.65));
+        assert!(close(absolute_humidity(0.0, 100.0), 4.85));
+        assert!(close(absolute_humidity(30.0, 80.0), 24.3));
+        assert_eq!(absolute_humidity(20.0, 0.0), 0.0);
+    }
+
+    /// Humidity ratios match a psychrometric chart to within 0.3 g/kg.
+    #[test]
+    fn humidity_ratios() {
+        let close = |a: f32, b: f32| (a - b).abs() < 0.3;
+        assert!(close(humidity_ratio(20.0, 50.0), 7.3));
+        assert!(close(humidity_ratio(25.0, 60.0), 11.9));
+        assert!(close(humidity_ratio(30.0, 100.0), 27.3));
+        assert_eq!(humidity_ratio(20.0, 0.0), 0.0);
+    }
+
+    /// The integer approximation is within 2.5C of the Magnus formula at high humidity, and too
+    /// high in dry air.
+    #[test]
+    fn dew_points_no_fp() {
+        for temperature in 0..=30 {
+            for humidity in 50..=100 {
+                let exact = dew_point(temperature as f32, humidity as f32);
+                let approximate = dew_point_no_fp(temperature, humidity) as f32;
+                assert!(
+                    (exact - approximate).abs() <= 2.5,
+                    "{temperature} {humidity}"
+                );
+            }
+        }
+        assert!(dew_point_no_fp(20, 40) as f32 - dew_point(20.0, 40.0) > 1.5);
+        assert!(dew_point_no_fp(20, 20) as f32 - dew_point(20.0, 20.0) > 7.0);
+        assert_eq!(dew_point_no_fp(20, 150), 20);
+    }
+}
@@ -0,0 +1,232 @@
+//! Resuming an initialized sensor after MCU deep sleep.
+//!
+//! On parts like the ESP32 and nRF52 deep sleep restarts the MCU, but the AHT20 stays powered and
+//! calibrated. Rather than run `init` again on every wake, keep a `RetainedState` in RAM that
+//! survives deep sleep, and `resume` from it:
+//!
+//!     # use embedded_hal_mock::eh1::delay::NoopDelay as MockDelay;
+//!     # use embedded_hal_mock::eh1::i2c::Mock as I2cMock;
+//!     # use embedded_hal_mock::eh1::i2c::Transaction;
+//!     use aht20_driver::{RetainedState, AHT20, SENSOR_ADDRESS};
+//!
+//!     # let expectations = vec![Transaction::read(SENSOR_ADDRESS, vec![0b0000_1000])];
+//!     # let mock_i2c = I2cMock::new(&expectations);
+//!     # let mut delay = MockDelay::new();
+//!     // In real use this lives in retained RAM, e.g. `#[link_section = ".rtc.data"]` on the
+//!     // ESP32, and starts out invalid.
+//!     let mut retained = RetainedState::INVALID;
+//!
+//!     let aht20 = AHT20::new(mock_i2c, SENSOR_ADDRESS);
+//!     let aht20 = match aht20.resume(&retained) {
+//!         Ok(ready) => ready,
+//!         // Nothing valid was retained, so this is a cold start.
+//!         Err(uninit) => uninit.init(&mut delay).unwrap(),
+//!     };
+//!     retained = aht20.retained_state();
+//!     # assert!(retained.is_valid());
+//!     # aht20.destroy().done();
+//!
+//! A resumed driver checks the calibration bit of the first measurement it takes. If the sensor
+//! has lost its calibration, for example because it was power cycled while the MCU slept, that
+//! measurement fails with `Error::Uncalibrated`, and `retained_state` returns an invalid state
+//! from then on so the next wake won't trust it either. Use `into_uninit` and `init` to recover.
+
+use embedded_hal::i2c::I2c;
+
+use crate::{compute_crc, Ready, Uninit, AHT20};
+
+/// Marks the first byte of a valid `RetainedState`.
+const MAGIC: u8 = 0xA2;
+
+/// The layout version of `RetainedState`, bumped if the meaning of the bytes changes.
+const VERSION: u8 = 1;
+
+/// Whether the driver knows the sensor to be calibrated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Calibration {
+    /// `init` ran, or a resumed driver has seen the calibration bit set.
+    Verified,
+    /// Resumed from a `RetainedState`, and not measured yet.
+    Unverified,
+    /// A resumed driver found the sensor uncalibrated.
+    Lost,
+}
+
+/// The driver's state, small enough to keep in retained RAM across deep sleep.
+///
+/// This is four bytes: a marker, the sensor's address, a layout version and a CRC over the other
+/// three. Use `as_bytes` and `from_bytes` if your retained memory is a byte buffer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "use-defmt", derive(defmt::Format))]
+#[repr(C)]
+pub struct RetainedState {
+    bytes: [u8; 4],
+}
+
+impl RetainedState {
+    /// A state that will never be resumed from. Initialize retained RAM with this.
+    pub const INVALID: RetainedState = RetainedState { bytes: [0; 4] };
+
+    /// Create the state for an initialized sensor at `address`.
+    fn new(address: u8) -> Self {
+        let mut bytes = [MAGIC, address, VERSION, 0];
+        bytes[3] = compute_crc(&bytes[..3]);
+        RetainedState { bytes }
+    }
+
+    /// Recreate a state from the bytes returned by `as_bytes`. The bytes are checked when
+    /// resuming, not here.
+    pub fn from_bytes(bytes: [u8; 4]) -> Self {
+        RetainedState { bytes }
+    }
+
+    /// The bytes to keep in retained memory.
+    pub fn as_bytes(&self) -> [u8; 4] {
+        self.bytes
+    }
+
+    /// Check that this state was created by the driver and hasn't been corrupted or invalidated.
+    pub fn is_valid(&self) -> bool {
+        self.bytes[0] == MAGIC
+            && self.bytes[2] == VERSION
+            && compute_crc(&self.bytes[..3]) == self.bytes[3]
+    }
+
+    /// Make sure this state is never resumed from.
+    pub fn invalidate(&mut self) {
+        *self = RetainedState::INVALID;
+    }
+
+    /// The sensor address this state was created for.
+    fn address(&self) -> u8 {
+        self.bytes[1]
+    }
+}
+
+impl<I> AHT20<I, Uninit>
+where
+    I: I2c,
+{
+    /// Skip `init` for a sensor that is still initialized from before an MCU deep sleep.
+    ///
+    /// This doesn't talk to the sensor. If `state` isn't valid, or is for a different address,
+    /// the uninitialized driver is handed back in `Err` so that you can call `init` instead. The
+    /// calibration bit is checked on the first measurement, see the module documentation.
+    pub fn resume(self, state: &RetainedState) -> Result<AHT20<I, Ready>, AHT20<I, Uninit>> {
+        if !state.is_valid() || state.address() != self.config.address {
+            #[cfg(feature = "use-defmt")]
+            defmt::debug!("resume: retained state not valid for this sensor.");
+            return Err(self);
+        }
+
+        let mut ready: AHT20<I, Ready> = self.into_state();
+        ready.calibration = Calibration::Unverified;
+        Ok(ready)
+    }
+}
+
+impl<I> AHT20<I, Ready>
+where
+    I: I2c,
+{
+    /// The state to keep in retained RAM, to `resume` from after deep sleep.
+    ///
+    /// This is `RetainedState::INVALID` if the sensor has been found to be uncalibrated.
+    pub fn retained_state(&self) -> RetainedState {
+        match self.calibration {
+            Calibration::Lost => RetainedState::INVALID,
+            Calibration::Verified | Calibration::Unverified => {
+                RetainedState::new(self.config.address)
+            }
+        }
+    }
+
+    /// Go back to the uninitialized state, for example to run `init` again after a measurement
+    /// returned `Error::Uncalibrated`.
+    pub fn into_uninit(self) -> AHT20<I, Uninit> {
+        self.into_state()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RetainedState;
+    use crate::test_support::{measurement, measurement_frame};
+    use crate::{compute_crc, Error, AHT20, SENSOR_ADDRESS};
+    use embedded_hal_mock::eh1::delay::NoopDelay as MockDelay;
+    use embedded_hal_mock::eh1::i2c::Mock as I2cMock;
+    use embedded_hal_mock::eh1::i2c::Transaction;
+
+    /// A state survives a round trip through bytes, and invalid states are rejected.
+    #[test]
+    fn retained_state_bytes() {
+        let state = RetainedState::new(SENSOR_ADDRESS);
+        assert!(state.is_valid());
+        assert_eq!(RetainedState::from_bytes(state.as_bytes()), state);
+
+        let mut corrupted = state.as_bytes();
+        corrupted[1] ^= 0b0000_0001;
+        assert!(!RetainedState::from_bytes(corrupted).is_valid());
+
+        let mut invalidated = state;
+        invalidated.invalidate();
+        assert!(!invalidated.is_valid());
+        assert!(!RetainedState::INVALID.is_valid());
+    }
+
+    /// Resuming from an invalid state or another address hands back the uninitialized driver.
+    #[test]
+    fn resume_rejects_invalid_state() {
+        let aht20 = AHT20::new(I2cMock::new(&[]), SENSOR_ADDRESS);
+        let aht20 = aht20.resume(&RetainedState::INVALID).err().unwrap();
+        let aht20 = aht20.resume(&RetainedState::new(0x39)).err().unwrap();
+
+        aht20.destroy().done();
+    }
+
+    /// A resumed driver measures as normal when the sensor is still calibrated.
+    #[test]
+    fn resume_calibrated() {
+        let mut expectations = measurement(measurement_frame());
+        expectations.extend(measurement(measurement_frame()));
+        let mock_i2c = I2cMock::new(&expectations);
+        let mut mock_delay = MockDelay::new();
+
+        let aht20 = AHT20::new(mock_i2c, SENSOR_ADDRESS);
+        let mut aht20 = aht20
+            .resume(&RetainedState::new(SENSOR_ADDRESS))
+            .ok()
+            .unwrap();
+        aht20.measure(&mut mock_delay).unwrap();
+        aht20.measure(&mut mock_delay).unwrap();
+        assert!(aht20.retained_state().is_valid());
+
+        aht20.destroy().done();
+    }
+
+    /// A resumed driver that finds the sensor uncalibrated errors, and invalidates its state.
+    #[test]
+    fn resume_uncalibrated() {
+        let mut frame = measurement_frame();
+        frame[0] = 0b0001_0100; // Calibration bit cleared.
+        frame[6] = compute_crc(&frame[..6]);
+        let mut expectations = measurement(frame);
+        // Running init again, the sensor is calibrated straight away.
+        expectations.push(Transaction::read(SENSOR_ADDRESS, vec![0b0001_1000]));
+        let mock_i2c = I2cMock::new(&expectations);
+        let mut mock_delay = MockDelay::new();
+
+        let aht20 = AHT20::new(mock_i2c, SENSOR_ADDRESS);
+        let mut aht20 = aht20
+            .resume(&RetainedState::new(SENSOR_ADDRESS))
+            .ok()
+            .unwrap();
+        assert_eq!(aht20.measure(&mut mock_delay), Err(Error::Uncalibrated));
+        assert!(!aht20.retained_state().is_valid());
+
+        let aht20 = aht20.into_uninit().init(&mut mock_delay).unwrap();
+        assert!(aht20.retained_state().is_valid());
+
+        aht20.destroy().done();
+    }
+}
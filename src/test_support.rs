@@ -0,0 +1,79 @@
+//! Fixtures shared by the unit tests.
+
+use embedded_hal_mock::eh1::i2c::Transaction;
+
+use crate::{compute_crc, Command, SensorReading, SENSOR_ADDRESS};
+
+/// A reading of `humidity` and `temperature`.
+pub(crate) fn reading(humidity: f32, temperature: f32) -> SensorReading {
+    SensorReading {
+        humidity,
+        temperature,
+    }
+}
+
+/// A reading of `temperature`, at 50% humidity.
+pub(crate) fn at_temperature(temperature: f32) -> SensorReading {
+    reading(50.0, temperature)
+}
+
+/// A reading of `humidity`, at 20°C.
+pub(crate) fn at_humidity(humidity: f32) -> SensorReading {
+    reading(humidity, 20.0)
+}
+
+/// The send_trigger_measurement transaction.
+pub(crate) fn trigger_measurement() -> Transaction {
+    Transaction::write(
+        SENSOR_ADDRESS,
+        vec![
+            Command::TriggerMeasurement as u8,
+            0b0011_0011, // 0x33
+            0b0000_0000, // 0x00
+        ],
+    )
+}
+
+/// A 7 byte frame from a run of the sensor, 22.52°C and 39.73%.
+pub(crate) fn measurement_frame() -> Vec<u8> {
+    vec![
+        0b0001_1100, //  28, 0x1c - ready, calibrated.
+        0b0110_0101, // 101, 0x65
+        0b1011_0100, // 180, 0xb4
+        0b0010_0101, //  37, 0x25
+        0b1100_1101, // 205, 0xcd
+        0b0010_0110, //  38, 0x26
+        0b1100_0110, // 198, 0xc6 - CRC
+    ]
+}
+
+/// A ready 7 byte frame with the given 20 bit raw humidity and temperature values, and a valid
+/// CRC.
+pub(crate) fn raw_frame(humidity: u32, temperature: u32) -> Vec<u8> {
+    let mut frame = vec![
+        0b0001_1100,
+        (humidity >> 12) as u8,
+        (humidity >> 4) as u8,
+        ((humidity << 4) as u8) | ((temperature >> 16) as u8 & 0x0F),
+        (temperature >> 8) as u8,
+        temperature as u8,
+        0,
+    ];
+    frame[6] = compute_crc(&frame[..6]);
+    frame
+}
+
+/// Reading a finished measurement: a ready status repeating the frame's, then `frame`.
+pub(crate) fn read_measurement(frame: Vec<u8>) -> Vec<Transaction> {
+    vec![
+        Transaction::read(SENSOR_ADDRESS, vec![frame[0]]),
+        Transaction::read(SENSOR_ADDRESS, frame),
+    ]
+}
+
+/// A whole measurement returning `frame`, the trigger followed by `read_measurement`.
+pub(crate) fn measurement(frame: Vec<u8>) -> Vec<Transaction> {
+    let mut transactions = vec![trigger_measurement()];
+    transactions.extend(read_measurement(frame));
+    transactions
+}
@@ -0,0 +1,200 @@
+//! Trends, and when a threshold will be crossed.
+//!
+//! `TrendEstimator` fits a least-squares line through the last `N` timestamped readings. The
+//! slope gives the rate of change, and following the line forward gives an estimate of when a
+//! threshold will be crossed, to warn before it has been:
+//!
+//!     use aht20_driver::{Quantity, SensorReading, TrendEstimator};
+//!
+//!     let mut trend: TrendEstimator<16> = TrendEstimator::new();
+//!     // Humidity rising 1% every minute.
+//!     for minute in 0..10u64 {
+//!         let reading = SensorReading { humidity: 50.0 + minute as f32, temperature: 21.0 };
+//!         trend.push(reading, minute * 60_000);
+//!     }
+//!
+//!     let per_hour = trend.rate_per_hour(Quantity::Humidity).unwrap();
+//!     assert!((per_hour - 60.0).abs() < 0.01);
+//!     // 59% now, so 70% is 11 minutes away.
+//!     let time_to_70_ms = trend.time_to_threshold_ms(Quantity::Humidity, 70.0).unwrap();
+//!     assert!(time_to_70_ms.abs_diff(11 * 60_000) < 1000);
+//!
+//! The window should cover long enough to see through the sensor's jitter, a few minutes for
+//! readings every few seconds.
+
+use heapless::HistoryBuffer;
+
+use crate::{Quantity, SensorReading, TimestampedReading};
+
+/// Milliseconds in an hour.
+const HOUR_MS: f32 = 3_600_000.0;
+
+/// A least-squares line, with time in hours since `origin_ms`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Line {
+    origin_ms: u64,
+    slope_per_hour: f32,
+    intercept: f32,
+}
+
+/// Estimates trends over the last `N` readings.
+pub struct TrendEstimator<const N: usize> {
+    readings: HistoryBuffer<TimestampedReading, N>,
+}
+
+impl<const N: usize> TrendEstimator<N> {
+    /// An estimator with no readings.
+    pub fn new() -> Self {
+        TrendEstimator {
+            readings: HistoryBuffer::new(),
+        }
+    }
+
+    /// Add a reading taken at `timestamp_ms`, replacing the oldest if the window is full.
+    ///
+    /// A reading older than the latest one is ignored.
+    pub fn push(&mut self, reading: SensorReading, timestamp_ms: u64) {
+        if self
+            .readings
+            .recent()
+            .is_some_and(|latest| timestamp_ms < latest.timestamp_ms)
+        {
+            return;
+        }
+        self.readings.write(TimestampedReading {
+            reading,
+            timestamp_ms,
+        });
+    }
+
+    /// Forget all readings.
+    pub fn clear(&mut self) {
+        self.readings.clear();
+    }
+
+    /// The rate of change of `quantity`, in its units per hour.
+    ///
+    /// `None` until there are two readings taken at different times.
+    pub fn rate_per_hour(&self, quantity: Quantity) -> Option<f32> {
+        self.fit(quantity).map(|line| line.slope_per_hour)
+    }
+
+    /// How long after the latest reading `quantity` is projected to reach `threshold`, in
+    /// milliseconds.
+    ///
+    /// This is `None` if the trend line is flat or moving away from the threshold, which includes
+    /// having already passed it, or if there aren't enough readings to tell. Check the latest
+    /// reading to tell whether the threshold has already been crossed.
+    pub fn time_to_threshold_ms(&self, quantity: Quantity, threshold: f32) -> Option<u64> {
+        let line = self.fit(quantity)?;
+        if line.slope_per_hour == 0.0 {
+            return None;
+        }
+
+        let latest_ms = self.readings.recent()?.timestamp_ms;
+        let now_hours = latest_ms.saturating_sub(line.origin_ms) as f32 / HOUR_MS;
+        let value_now = line.intercept + line.slope_per_hour * now_hours;
+        let hours = (threshold - value_now) / line.slope_per_hour;
+        (hours >= 0.0).then_some((hours * HOUR_MS) as u64)
+    }
+
+    /// Fit a least-squares line through the readings of `quantity`.
+    fn fit(&self, quantity: Quantity) -> Option<Line> {
+        let origin_ms = self.readings.oldest_ordered().next()?.timestamp_ms;
+        let points = || {
+            self.readings.oldest_ordered().map(move |r| {
+                (
+                    r.timestamp_ms.saturating_sub(origin_ms) as f32 / HOUR_MS,
+                    r.reading.get(quantity),
+                )
+            })
+        };
+
+        let count = self.readings.len() as f32;
+        let (sum_t, sum_v) = points().fold((0.0, 0.0), |(st, sv), (t, v)| (st + t, sv + v));
+        let (mean_t, mean_v) = (sum_t / count, sum_v / count);
+        let (s_tt, s_tv) = points().fold((0.0, 0.0), |(stt, stv), (t, v)| {
+            let dt = t - mean_t;
+            (stt + dt * dt, stv + dt * (v - mean_v))
+        });
+        if s_tt <= 0.0 {
+            return None;
+        }
+
+        let slope_per_hour = s_tv / s_tt;
+        Some(Line {
+            origin_ms,
+            slope_per_hour,
+            intercept: mean_v - slope_per_hour * mean_t,
+        })
+    }
+}
+
+impl<const N: usize> Default for TrendEstimator<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TrendEstimator;
+    use crate::test_support::reading;
+    use crate::Quantity;
+
+    /// The slope of a noisy rising line is found.
+    #[test]
+    fn rate_per_hour() {
+        let mut trend: TrendEstimator<8> = TrendEstimator::new();
+        assert_eq!(trend.rate_per_hour(Quantity::Temperature), None);
+        trend.push(reading(50.0, 20.0), 0);
+        trend.push(reading(50.0, 20.0), 0);
+        assert_eq!(trend.rate_per_hour(Quantity::Temperature), None);
+
+        trend.clear();
+        for minute in 0..12u64 {
+            let jitter = if minute % 2 == 0 { 0.05 } else { -0.05 };
+            // Temperature falling 2C an hour, humidity steady.
+            let temperature = 20.0 - minute as f32 / 30.0 + jitter;
+            trend.push(reading(50.0, temperature), minute * 60_000);
+        }
+        let rate = trend.rate_per_hour(Quantity::Temperature).unwrap();
+        assert!((rate + 2.0).abs() < 0.5, "{rate}");
+        assert_eq!(trend.rate_per_hour(Quantity::Humidity), Some(0.0));
+    }
+
+    /// The time to a threshold follows the trend, and is None when moving away.
+    #[test]
+    fn time_to_threshold() {
+        let mut trend: TrendEstimator<4> = TrendEstimator::new();
+        // Humidity rising 6% an hour, the oldest reading is dropped from the window.
+        for minute in [0, 10, 20, 30, 40u64] {
+            trend.push(reading(60.0 + minute as f32 / 10.0, 20.0), minute * 60_000);
+        }
+
+        let time_ms = trend
+            .time_to_threshold_ms(Quantity::Humidity, 70.0)
+            .unwrap();
+        assert!(time_ms.abs_diff(60 * 60_000) < 1000, "{time_ms}");
+        // Already past 50%, and moving away from it.
+        assert_eq!(trend.time_to_threshold_ms(Quantity::Humidity, 50.0), None);
+        assert_eq!(
+            trend.time_to_threshold_ms(Quantity::Temperature, 25.0),
+            None
+        );
+    }
+
+    /// Readings older than the latest are ignored, rather than bending the line.
+    #[test]
+    fn ignores_out_of_order_readings() {
+        let mut trend: TrendEstimator<4> = TrendEstimator::new();
+        trend.push(reading(50.0, 20.0), 1000);
+        trend.push(reading(51.0, 20.0), 2000);
+        trend.push(reading(99.0, 20.0), 500);
+
+        let time_ms = trend
+            .time_to_threshold_ms(Quantity::Humidity, 52.0)
+            .unwrap();
+        assert_eq!(time_ms, 1000);
+    }
+}
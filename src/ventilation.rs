@@ -0,0 +1,264 @@
+//! Whether to ventilate, from an indoor and an outdoor sensor.
+//!
+//! Ventilating only dries a space when the outdoor air holds less water than the indoor air.
+//! Comparing relative humidity gets this wrong whenever the two temperatures differ. Cold air at
+//! 90% can hold less water than a warm cellar at 60%, and warm summer air at 50% can hold more
+//! than a cool cellar at 70%. `VentilationAdvisor` compares absolute humidity instead:
+//!
+//!     use aht20_driver::{SensorReading, VentilationAdvice, VentilationAdvisor, VentilationSettings};
+//!
+//!     let advisor = VentilationAdvisor::new(VentilationSettings::default()).unwrap();
+//!     let cellar = SensorReading { humidity: 70.0, temperature: 14.0 };
+//!     let summer_afternoon = SensorReading { humidity: 50.0, temperature: 28.0 };
+//!     let assessment = advisor.advise(cellar, summer_afternoon);
+//!     // 8.4 g/m³ indoors, 13.6 g/m³ outdoors.
+//!     assert_eq!(assessment.advice, VentilationAdvice::WouldWet);
+//!
+//! The difference has to be at least `min_margin` to be worth ventilating for, and ventilation
+//! that would dry the space is held back when the outdoor air is outside the temperature guards,
+//! for example to keep a cellar from freezing, or when colder air would cool the space below a
+//! set point.
+
+use crate::SensorReading;
+
+/// Settings for `VentilationAdvisor`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "use-defmt", derive(defmt::Format))]
+pub struct VentilationSettings {
+    /// How much drier the outdoor air has to be to ventilate, in grams per cubic metre. The same
+    /// margin applies to `WouldWet`.
+    pub min_margin: f32,
+    /// Don't ventilate with outdoor air below this temperature, in degrees Celsius.
+    pub min_outdoor_temperature: Option<f32>,
+    /// Don't ventilate with outdoor air above this temperature, in degrees Celsius.
+    pub max_outdoor_temperature: Option<f32>,
+    /// Don't ventilate with colder outdoor air once the indoor temperature is at or below this set
+    /// point, in degrees Celsius. Warmer outdoor air is still let in.
+    pub min_indoor_temperature: Option<f32>,
+}
+
+impl Default for VentilationSettings {
+    fn default() -> Self {
+        VentilationSettings {
+            min_margin: 1.0,
+            min_outdoor_temperature: Some(5.0),
+            max_outdoor_temperature: None,
+            min_indoor_temperature: None,
+        }
+    }
+}
+
+impl VentilationSettings {
+    /// Check that the margin is finite and not negative, and the guards are finite and the outdoor
+    /// ones in order.
+    fn is_valid(&self) -> bool {
+        let guard_valid = |guard: Option<f32>| guard.map_or(true, f32::is_finite);
+        let in_order = match (self.min_outdoor_temperature, self.max_outdoor_temperature) {
+            (Some(min), Some(max)) => min <= max,
+            _ => true,
+        };
+        self.min_margin.is_finite()
+            && self.min_margin >= 0.0
+            && guard_valid(self.min_outdoor_temperature)
+            && guard_valid(self.max_outdoor_temperature)
+            && guard_valid(self.min_indoor_temperature)
+            && in_order
+    }
+}
+
+/// What ventilating would do.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "use-defmt", derive(defmt::Format))]
+pub enum VentilationAdvice {
+    /// Ventilate: the outdoor air is drier by at least the margin.
+    Ventilate,
+    /// Don't ventilate: the outdoor air is wetter by at least the margin, and would make the space
+    /// damper.
+    WouldWet,
+    /// Don't ventilate: the difference is within the margin either way.
+    NoBenefit,
+    /// Don't ventilate: it would dry the space, but the outdoor air is below
+    /// `min_outdoor_temperature`.
+    TooCold,
+    /// Don't ventilate: it would dry the space, but the outdoor air is above
+    /// `max_outdoor_temperature`.
+    TooWarm,
+    /// Don't ventilate: it would dry the space, but the outdoor air is colder and the space is
+    /// already at or below `min_indoor_temperature`.
+    WouldCool,
+}
+
+/// The result of `VentilationAdvisor::advise`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "use-defmt", derive(defmt::Format))]
+pub struct VentilationAssessment {
+    /// The absolute humidity indoors, in grams per cubic metre.
+    pub indoor_absolute_humidity: f32,
+    /// The absolute humidity outdoors, in grams per cubic metre.
+    pub outdoor_absolute_humidity: f32,
+    /// Whether to ventilate.
+    pub advice: VentilationAdvice,
+}
+
+/// The error from `VentilationAdvisor::new`, for a negative margin, or a temperature guard that
+/// isn't finite or is out of order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "use-defmt", derive(defmt::Format))]
+pub struct InvalidVentilationSettings;
+
+impl core::fmt::Display for InvalidVentilationSettings {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> Result<(), core::fmt::Error> {
+        write!(f, "invalid ventilation settings")
+    }
+}
+
+impl core::error::Error for InvalidVentilationSettings {}
+
+/// Advises whether ventilating will dry or wet a space.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VentilationAdvisor {
+    settings: VentilationSettings,
+}
+
+impl VentilationAdvisor {
+    /// Create an advisor. Returns `InvalidVentilationSettings` if the margin is negative or not
+    /// finite, a guard isn't finite, or the minimum outdoor temperature is above the maximum.
+    pub fn new(settings: VentilationSettings) -> Result<Self, InvalidVentilationSettings> {
+        if !settings.is_valid() {
+            return Err(InvalidVentilationSettings);
+        }
+        Ok(VentilationAdvisor { settings })
+    }
+
+    /// Compare an indoor and an outdoor reading.
+    pub fn advise(&self, indoor: SensorReading, outdoor: SensorReading) -> VentilationAssessment {
+        let indoor_absolute_humidity = indoor.absolute_humidity();
+        let outdoor_absolute_humidity = outdoor.absolute_humidity();
+        let drying = indoor_absolute_humidity - outdoor_absolute_humidity;
+
+        let too_cold = self
+            .settings
+            .min_outdoor_temperature
+            .is_some_and(|min| outdoor.temperature < min);
+        let too_warm = self
+            .settings
+            .max_outdoor_temperature
+            .is_some_and(|max| outdoor.temperature > max);
+        let would_cool = self.settings.min_indoor_temperature.is_some_and(|min| {
+            outdoor.temperature < indoor.temperature && indoor.temperature <= min
+        });
+
+        let advice = if drying == 0.0 || drying.abs() < self.settings.min_margin {
+            VentilationAdvice::NoBenefit
+        } else if drying < 0.0 {
+            VentilationAdvice::WouldWet
+        } else if too_cold {
+            VentilationAdvice::TooCold
+        } else if too_warm {
+            VentilationAdvice::TooWarm
+        } else if would_cool {
+            VentilationAdvice::WouldCool
+        } else {
+            VentilationAdvice::Ventilate
+        };
+
+        VentilationAssessment {
+            indoor_absolute_humidity,
+            outdoor_absolute_humidity,
+            advice,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        InvalidVentilationSettings, VentilationAdvice, VentilationAdvisor, VentilationSettings,
+    };
+    use crate::test_support::reading;
+
+    /// Advice follows absolute humidity, not relative humidity.
+    #[test]
+    fn compares_absolute_humidity() {
+        let advisor = VentilationAdvisor::new(VentilationSettings::default()).unwrap();
+        // 8.4 g/m³.
+        let cellar = reading(70.0, 14.0);
+
+        // Damp-looking but cold air, 6.5 g/m³.
+        let assessment = advisor.advise(cellar, reading(90.0, 6.0));
+        assert_eq!(assessment.advice, VentilationAdvice::Ventilate);
+        assert!(assessment.indoor_absolute_humidity > assessment.outdoor_absolute_humidity);
+
+        // Dry-looking but warm air, 13.6 g/m³.
+        let advice = advisor.advise(cellar, reading(50.0, 28.0)).advice;
+        assert_eq!(advice, VentilationAdvice::WouldWet);
+
+        // 8.2 g/m³, too close to be worth it.
+        let advice = advisor.advise(cellar, reading(60.0, 16.0)).advice;
+        assert_eq!(advice, VentilationAdvice::NoBenefit);
+    }
+
+    /// Ventilation that would dry is held back outside the temperature guards.
+    #[test]
+    fn temperature_guards() {
+        let advisor = VentilationAdvisor::new(VentilationSettings {
+            min_margin: 0.5,
+            min_outdoor_temperature: Some(5.0),
+            max_outdoor_temperature: Some(20.0),
+            min_indoor_temperature: None,
+        })
+        .unwrap();
+        let cellar = reading(80.0, 14.0);
+
+        let advice = advisor.advise(cellar, reading(80.0, 2.0)).advice;
+        assert_eq!(advice, VentilationAdvice::TooCold);
+        let advice = advisor.advise(cellar, reading(20.0, 24.0)).advice;
+        assert_eq!(advice, VentilationAdvice::TooWarm);
+        let advice = advisor.advise(cellar, reading(50.0, 15.0)).advice;
+        assert_eq!(advice, VentilationAdvice::Ventilate);
+    }
+
+    /// Colder air is held back once the space has cooled to the indoor set point.
+    #[test]
+    fn indoor_set_point() {
+        let advisor = VentilationAdvisor::new(VentilationSettings {
+            min_indoor_temperature: Some(10.0),
+            ..VentilationSettings::default()
+        })
+        .unwrap();
+        let outdoor = reading(70.0, 6.0);
+
+        // Above the set point, cooling towards it is fine.
+        let advice = advisor.advise(reading(80.0, 12.0), outdoor).advice;
+        assert_eq!(advice, VentilationAdvice::Ventilate);
+        let advice = advisor.advise(reading(90.0, 10.0), outdoor).advice;
+        assert_eq!(advice, VentilationAdvice::WouldCool);
+        // Warmer, drier air is still let in.
+        let advice = advisor
+            .advise(reading(90.0, 9.0), reading(40.0, 15.0))
+            .advice;
+        assert_eq!(advice, VentilationAdvice::Ventilate);
+    }
+
+    /// Negative margins and guards out of order are rejected.
+    #[test]
+    fn rejects_invalid_settings() {
+        let settings = VentilationSettings {
+            min_margin: -1.0,
+            ..VentilationSettings::default()
+        };
+        assert_eq!(
+            VentilationAdvisor::new(settings),
+            Err(InvalidVentilationSettings)
+        );
+        let settings = VentilationSettings {
+            min_outdoor_temperature: Some(10.0),
+            max_outdoor_temperature: Some(5.0),
+            ..VentilationSettings::default()
+        };
+        assert_eq!(
+            VentilationAdvisor::new(settings),
+            Err(InvalidVentilationSettings)
+        );
+    }
+}